@@ -0,0 +1,76 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use gtk::glib;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+
+use crate::Message;
+
+/*
+ * A D-Bus interface to look into the backend while debugging. `ListActiveRequests` asks the `GLib`
+ * world for the requests that still have a responder, which helps to tell why a dialog is stuck.
+ * `CancelAppRequests` closes the dialogs of an app, like a force stop of the app would.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-debug";
+
+pub struct Debug {
+    sender: Sender<Message>,
+}
+
+impl Debug {
+    pub const OBJECT_PATH: &'static str = "/mobi/phosh/xdpp/Debug";
+
+    #[must_use]
+    pub fn new(sender: Sender<Message>) -> Self {
+        Debug { sender }
+    }
+}
+
+#[zbus::interface(name = "mobi.phosh.xdpp.Debug")]
+impl Debug {
+    /// List the request id, interface name and app-id of each active request. The app-id is
+    /// empty when it is not known.
+    async fn list_active_requests(&self) -> zbus::fdo::Result<Vec<(u64, String, String)>> {
+        let (sender, receiver) = oneshot::channel();
+        if let Err(error) = self.sender.send(Message::ListRequests { sender }).await {
+            glib::g_critical!(LOG_DOMAIN, "Error: {error}");
+            return Err(zbus::fdo::Error::Failed(String::from(
+                "Unable to reach the backend",
+            )));
+        }
+
+        let requests = receiver.await.map_err(|error| {
+            glib::g_critical!(LOG_DOMAIN, "Error: {error}");
+            zbus::fdo::Error::Failed(String::from("Backend did not reply"))
+        })?;
+
+        Ok(requests
+            .into_iter()
+            .map(|info| {
+                (
+                    info.request_id as u64,
+                    String::from(info.interface),
+                    info.app_id.unwrap_or_default(),
+                )
+            })
+            .collect())
+    }
+
+    /// Cancel every active request of the app with `app_id`. Requests of other apps stay open.
+    async fn cancel_app_requests(&self, app_id: String) -> zbus::fdo::Result<()> {
+        if let Err(error) = self.sender.send(Message::CancelApp { app_id }).await {
+            glib::g_critical!(LOG_DOMAIN, "Error: {error}");
+            return Err(zbus::fdo::Error::Failed(String::from(
+                "Unable to reach the backend",
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::collections::HashMap;
+
+use ashpd::AppID;
+use gtk::prelude::*;
+use gtk::{gio, glib};
+
+use crate::Interface;
+
+/*
+ * Remembered decisions of permission dialogs.
+ *
+ * When the user asks to remember their choice, the decision is kept in the `access-decisions` key
+ * of the `mobi.phosh.xdpp` schema, as a map from the decision key to whether access was granted.
+ * The key ties the decision to the app, the interface and the resource, so that only an identical
+ * request gets the remembered decision. The resource is given as a checksum, so that the settings
+ * do not leak what the app asked for.
+ *
+ * Without the schema installed, like when running from the build directory, nothing is remembered.
+ * `gsettings reset mobi.phosh.xdpp access-decisions` forgets all decisions.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-decisions";
+
+const SCHEMA: &str = "mobi.phosh.xdpp";
+const DECISIONS_KEY: &str = "access-decisions";
+
+/// The key of the decision about `resource` for the app with `app_id` through `interface`. Apps
+/// without an app-id can not be told apart, so their decisions are not remembered.
+#[must_use]
+pub fn decision_key(
+    app_id: Option<&AppID>,
+    interface: Interface,
+    resource: &[&str],
+) -> Option<String> {
+    let app_id = app_id?;
+    let checksum =
+        glib::compute_checksum_for_string(glib::ChecksumType::Sha256, resource.join("\0"))?;
+    Some(format!("{app_id} {} {checksum}", interface.name()))
+}
+
+fn settings() -> Option<gio::Settings> {
+    let has_key = gio::SettingsSchemaSource::default()
+        .and_then(|source| source.lookup(SCHEMA, true))
+        .is_some_and(|schema| schema.has_key(DECISIONS_KEY));
+    if !has_key {
+        glib::g_debug!(LOG_DOMAIN, "No {SCHEMA} schema, not remembering decisions");
+        return None;
+    }
+    Some(gio::Settings::new(SCHEMA))
+}
+
+fn decisions(settings: &gio::Settings) -> HashMap<String, bool> {
+    settings.value(DECISIONS_KEY).get().unwrap_or_default()
+}
+
+/// Whether decisions can be remembered at all.
+#[must_use]
+pub fn can_remember() -> bool {
+    settings().is_some()
+}
+
+/// The remembered decision of `key`. `Some(true)` means access was granted.
+#[must_use]
+pub fn remembered(key: &str) -> Option<bool> {
+    let granted = decisions(&settings()?).get(key).copied();
+    if let Some(granted) = granted {
+        glib::g_debug!(LOG_DOMAIN, "Remembered decision for {key}: {granted}");
+    }
+    granted
+}
+
+/// Remember the decision of `key`, replacing an earlier one.
+pub fn remember(key: &str, granted: bool) {
+    let Some(settings) = settings() else {
+        return;
+    };
+    let mut decisions = decisions(&settings);
+    decisions.insert(key.to_string(), granted);
+    if let Err(error) = settings.set_value(DECISIONS_KEY, &decisions.to_variant()) {
+        glib::g_warning!(LOG_DOMAIN, "Unable to remember decision: {error}");
+        return;
+    }
+    // The backend may exit when idle, so write the decision out right away.
+    gio::Settings::sync();
+    glib::g_debug!(LOG_DOMAIN, "Remembered decision for {key}: {granted}");
+}
+
+/// Forget the decision of `key`, if any.
+pub fn forget(key: &str) {
+    let Some(settings) = settings() else {
+        return;
+    };
+    let mut decisions = decisions(&settings);
+    if decisions.remove(key).is_none() {
+        return;
+    }
+    if let Err(error) = settings.set_value(DECISIONS_KEY, &decisions.to_variant()) {
+        glib::g_warning!(LOG_DOMAIN, "Unable to forget decision: {error}");
+        return;
+    }
+    gio::Settings::sync();
+    glib::g_debug!(LOG_DOMAIN, "Forgot decision for {key}");
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_decision_key() {
+        let app_id = AppID::from_str("mobi.phosh.Test").unwrap();
+        let key = |resource: &[&str]| decision_key(Some(&app_id), Interface::Access, resource);
+
+        let camera = key(&["Allow Camera?", "", "Test wants to use the camera."]).unwrap();
+        assert!(camera.starts_with("mobi.phosh.Test org.freedesktop.impl.portal.Access "));
+        assert_eq!(
+            key(&["Allow Camera?", "", "Test wants to use the camera."]),
+            Some(camera.clone())
+        );
+        assert_ne!(
+            key(&["Allow Microphone?", "", "Test wants to use the camera."]),
+            Some(camera.clone())
+        );
+        // Fields are kept apart, so moving text between them makes another resource.
+        assert_ne!(
+            key(&["Allow Camera?", "Test wants to use the camera.", ""]),
+            Some(camera)
+        );
+
+        assert_eq!(
+            decision_key(None, Interface::Access, &["Allow Camera?"]),
+            None
+        );
+    }
+}
@@ -0,0 +1,76 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::lib_config;
+
+/*
+ * Defaults of the responders for when a request does not say. They start out with the values from
+ * the build configuration, and the binary can override them at startup, like from its configuration
+ * file.
+ */
+
+static DEFAULT_MODAL: AtomicBool = AtomicBool::new(lib_config::DEFAULT_MODAL);
+static XDG_DEFAULT_FOLDERS: AtomicBool = AtomicBool::new(lib_config::XDG_DEFAULT_FOLDERS);
+static SHORTCUT_FOLDERS: RwLock<Vec<PathBuf>> = RwLock::new(Vec::new());
+static APP_CHOOSER_MODAL: RwLock<Option<bool>> = RwLock::new(None);
+
+/// Modality of portal dialogs when the request does not ask for any.
+pub fn default_modal() -> bool {
+    DEFAULT_MODAL.load(Ordering::Relaxed)
+}
+
+pub fn set_default_modal(modal: bool) {
+    DEFAULT_MODAL.store(modal, Ordering::Relaxed);
+}
+
+/// Modality of app choosers when the request does not ask for any, if it differs from
+/// [`default_modal`]. A chooser left behind the app is easily lost on a phone.
+///
+/// # Panics
+///
+/// Panics if another thread panicked while setting the modality.
+pub fn app_chooser_modal() -> Option<bool> {
+    *APP_CHOOSER_MODAL.read().unwrap()
+}
+
+/// # Panics
+///
+/// Panics if another thread panicked while reading the modality.
+pub fn set_app_chooser_modal(modal: Option<bool>) {
+    *APP_CHOOSER_MODAL.write().unwrap() = modal;
+}
+
+/// Whether file choosers without a folder start in the XDG documents and downloads folders instead
+/// of the home folder.
+pub fn xdg_default_folders() -> bool {
+    XDG_DEFAULT_FOLDERS.load(Ordering::Relaxed)
+}
+
+pub fn set_xdg_default_folders(xdg: bool) {
+    XDG_DEFAULT_FOLDERS.store(xdg, Ordering::Relaxed);
+}
+
+/// Extra folders file choosers offer as shortcuts, before the removable media.
+///
+/// # Panics
+///
+/// Panics if another thread panicked while setting the folders.
+pub fn shortcut_folders() -> Vec<PathBuf> {
+    SHORTCUT_FOLDERS.read().unwrap().clone()
+}
+
+/// # Panics
+///
+/// Panics if another thread panicked while reading the folders.
+pub fn set_shortcut_folders(folders: Vec<PathBuf>) {
+    *SHORTCUT_FOLDERS.write().unwrap() = folders;
+}
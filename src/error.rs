@@ -0,0 +1,71 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::fmt;
+
+use ashpd::PortalError;
+
+/*
+ * Failures of the backend itself, as opposed to the user cancelling or denying a request. They are
+ * kept apart from `PortalError` so that the code and its tests can tell them apart by kind, and are
+ * turned into the matching `PortalError` only when replying.
+ */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XdppError {
+    /// The request could not be passed on, or it was dropped without a reply.
+    ChannelClosed(String),
+    /// No responder handles the request, like an update of a request that is already gone.
+    ResponderMissing(String),
+    /// The request is malformed or refers to something unknown.
+    InvalidRequest(String),
+    /// Something unexpected went wrong in the backend.
+    Internal(String),
+}
+
+impl fmt::Display for XdppError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ChannelClosed(message)
+            | Self::ResponderMissing(message)
+            | Self::InvalidRequest(message)
+            | Self::Internal(message) => formatter.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for XdppError {}
+
+impl From<XdppError> for PortalError {
+    fn from(error: XdppError) -> Self {
+        match error {
+            XdppError::ChannelClosed(message) | XdppError::Internal(message) => {
+                PortalError::Failed(message)
+            }
+            XdppError::ResponderMissing(message) => PortalError::NotFound(message),
+            XdppError::InvalidRequest(message) => PortalError::InvalidArgument(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_into_portal_error() {
+        let error = PortalError::from(XdppError::ChannelClosed(String::from("closed")));
+        assert!(matches!(error, PortalError::Failed(message) if message == "closed"));
+        let error = PortalError::from(XdppError::ResponderMissing(String::from("missing")));
+        assert!(matches!(error, PortalError::NotFound(message) if message == "missing"));
+        let error = PortalError::from(XdppError::InvalidRequest(String::from("invalid")));
+        assert!(matches!(error, PortalError::InvalidArgument(message) if message == "invalid"));
+        let error = PortalError::from(XdppError::Internal(String::from("internal")));
+        assert!(matches!(error, PortalError::Failed(message) if message == "internal"));
+    }
+}
@@ -6,11 +6,14 @@
  * Author: Arun Mani J <arun.mani@tether.to>
  */
 
+use std::cell::OnceCell;
 use std::env;
+use std::error::Error;
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use adw::prelude::*;
 use gettextrs::{bind_textdomain_codeset, bindtextdomain};
-use gtk::gio;
+use gtk::{gio, glib};
 
 use crate::lib_config::{GETTEXT_PACKAGE, LOCALE_DIR};
 
@@ -18,33 +21,89 @@ use crate::lib_config::{GETTEXT_PACKAGE, LOCALE_DIR};
  * The entry-point to the backend library.
  *
  * The `init` function initializes the library. It disables portals, initializes Adwaita, sets up
- * the `gettext` domain and registers resources.
+ * the `gettext` domain and registers resources. With portals disabled, Adwaita can not learn the
+ * color scheme from the Settings portal, so it is read from GSettings and followed instead.
  *
  * `i18n_init` can be used to exclusively set up the `gettext` domain.
+ *
+ * Both report failures to the caller instead of panicking, so that the binary can exit cleanly.
  */
 
+const LOG_DOMAIN: &str = "xdpp-init";
+
+const INTERFACE_SCHEMA: &str = "org.gnome.desktop.interface";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
 static LIB_INITIALIZED: AtomicBool = AtomicBool::new(false);
 static I18N_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
-#[allow(clippy::missing_panics_doc)]
-pub fn i18n_init() {
-    if I18N_INITIALIZED.load(Ordering::Acquire) {
+thread_local! {
+    static INTERFACE_SETTINGS: OnceCell<gio::Settings> = const { OnceCell::new() };
+}
+
+/// Map the `color-scheme` setting to Adwaita. `default` means no preference, which is light.
+fn color_scheme(value: &str) -> adw::ColorScheme {
+    match value {
+        "prefer-dark" => adw::ColorScheme::PreferDark,
+        "prefer-light" => adw::ColorScheme::PreferLight,
+        _ => adw::ColorScheme::Default,
+    }
+}
+
+fn follow_color_scheme() {
+    let has_key = gio::SettingsSchemaSource::default()
+        .and_then(|source| source.lookup(INTERFACE_SCHEMA, true))
+        .is_some_and(|schema| schema.has_key(COLOR_SCHEME_KEY));
+    if !has_key {
+        glib::g_debug!(LOG_DOMAIN, "No color scheme setting, using the default");
         return;
     }
 
-    bindtextdomain(GETTEXT_PACKAGE, LOCALE_DIR).unwrap();
-    bind_textdomain_codeset(GETTEXT_PACKAGE, "UTF-8").unwrap();
+    let settings = gio::Settings::new(INTERFACE_SCHEMA);
+    let update = |settings: &gio::Settings| {
+        let value = settings.string(COLOR_SCHEME_KEY);
+        glib::g_debug!(LOG_DOMAIN, "Color scheme is {value}");
+        adw::StyleManager::default().set_color_scheme(color_scheme(&value));
+    };
+    update(&settings);
+    settings.connect_changed(Some(COLOR_SCHEME_KEY), move |settings, _| update(settings));
+
+    // The settings only notify changes while they are alive.
+    INTERFACE_SETTINGS.with(|cell| {
+        let _ = cell.set(settings);
+    });
+}
+
+/// Set up the `gettext` domain.
+///
+/// # Errors
+///
+/// Returns an error if the domain could not be bound.
+pub fn i18n_init() -> Result<(), Box<dyn Error>> {
+    if I18N_INITIALIZED.load(Ordering::Acquire) {
+        return Ok(());
+    }
+
+    bindtextdomain(GETTEXT_PACKAGE, LOCALE_DIR)
+        .map_err(|error| format!("Unable to bind text domain: {error}"))?;
+    bind_textdomain_codeset(GETTEXT_PACKAGE, "UTF-8")
+        .map_err(|error| format!("Unable to set text domain codeset: {error}"))?;
 
     I18N_INITIALIZED.store(true, Ordering::Release);
+    Ok(())
 }
 
-#[allow(clippy::missing_panics_doc)]
-pub fn init() {
+/// Initialize the library.
+///
+/// # Errors
+///
+/// Returns an error if translations, Adwaita or the resources could not be set up.
+pub fn init() -> Result<(), Box<dyn Error>> {
     if LIB_INITIALIZED.load(Ordering::Acquire) {
-        return;
+        return Ok(());
     }
 
-    i18n_init();
+    i18n_init()?;
 
     gtk::disable_portals();
 
@@ -52,14 +111,29 @@ pub fn init() {
         env::set_var("ADW_DISABLE_PORTAL", "1");
     }
 
-    adw::init().unwrap();
+    adw::init().map_err(|error| format!("Unable to initialize Adwaita: {error}"))?;
+    follow_color_scheme();
 
     gio::resources_register_include_impl(include_bytes!(concat!(
         env!("RESOURCES_DIR"),
         "/",
         "xdg-desktop-portal-phrosh.gresource"
     )))
-    .unwrap();
+    .map_err(|error| format!("Unable to register resources: {error}"))?;
 
     LIB_INITIALIZED.store(true, Ordering::Release);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_color_scheme() {
+        assert_eq!(color_scheme("prefer-dark"), adw::ColorScheme::PreferDark);
+        assert_eq!(color_scheme("prefer-light"), adw::ColorScheme::PreferLight);
+        assert_eq!(color_scheme("default"), adw::ColorScheme::Default);
+        assert_eq!(color_scheme("unknown"), adw::ColorScheme::Default);
+    }
 }
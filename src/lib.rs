@@ -6,6 +6,10 @@
  * Author: Arun Mani J <arun.mani@tether.to>
  */
 
+mod debug;
+mod decisions;
+pub mod defaults;
+mod error;
 mod init;
 mod lib_config;
 mod message;
@@ -14,10 +18,14 @@ mod requester;
 pub mod requesters;
 mod responder;
 pub mod responders;
+mod session;
 pub mod utils;
 
+pub use debug::Debug;
+pub use error::XdppError;
 pub use init::{i18n_init, init};
-pub use message::Message;
-pub use request::{Application, Request};
-pub use requester::Requester;
-pub use responder::Responder;
+pub use message::{Message, RequestInfo};
+pub use request::{Application, Interface, Request, Route};
+pub use requester::{PendingRequest, RequestMap, Requester};
+pub use responder::{Reply, Responder};
+pub use session::{Session, Sessions};
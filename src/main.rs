@@ -7,15 +7,27 @@
  */
 
 use std::boxed::Box;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::pin::Pin;
 use std::process::ExitCode;
+use std::rc::Rc;
+use std::time::Duration;
 
+use ashpd::zbus::export::futures_core::Stream;
 use ashpd::zbus::fdo::RequestNameFlags;
+use ashpd::zbus::names::{BusName, InterfaceName};
+use ashpd::PortalError;
 use gtk::glib;
 use tokio::runtime::Runtime;
 use tokio::sync::mpsc;
 use xdg_desktop_portal_phosh::utils::gettextf;
-use xdg_desktop_portal_phosh::{requesters, responders, Message, Request, Requester, Responder};
+use xdg_desktop_portal_phosh::{
+    defaults, requesters, responders, Debug, Interface, Message, Request, RequestInfo, Requester,
+    Responder, Route, XdppError,
+};
 
 mod bin_config;
 
@@ -34,14 +46,33 @@ mod bin_config;
 
 const LOG_DOMAIN: &str = "xdpp";
 
+const FRONTEND_NAME: &str = "org.freedesktop.portal.Desktop";
+const FRONTEND_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// How many times to try acquiring the D-Bus name before giving up.
+const NAME_ATTEMPTS: u32 = 5;
+/// The delay before the first retry, doubled after each attempt.
+const NAME_RETRY_DELAY: Duration = Duration::from_millis(250);
+/// How long a request may stay open before the watchdog warns about it.
+const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
 const HELP: &str = "Usage:
   {} [OPTION…]
 
 A backend implementation of XDG Desktop Portal for Phosh environment in Rust.
 
+  --config=PATH\t\tRead interfaces and defaults from the key file at PATH.
+  --dbus-name=NAME\tOwn NAME on the session bus instead of the default.
+  --disable=NAMES\tDo not provide the comma separated interfaces in NAMES.
   -h, --help\t\tPrint this help and exit.
+  --idle-exit=SECONDS\tExit after SECONDS without requests or open windows.
+  --log-domain=DOMAIN\tWith --verbose, print debug information only from DOMAIN. Can be repeated.
+  --log-format=FORMAT\tPrint messages in FORMAT, default or compact for one line each.
+  --mock\t\tReply to requests with canned results, if built with the mock feature.
+  -q, --quiet\t\tPrint only warnings and errors.
   -r, --replace\t\tReplace existing instance.
-  -v, --verbose\t\tPrint debug information.
+  --self-test\t\tBuild a responder of each enabled interface and exit with the result.
+  -v, --verbose\t\tPrint debug information and serve a debug interface.
   --version\t\tPrint version information and exit.
 
 XDG Desktop Portal allow Flatpak apps, and other desktop containment frameworks, to interact with
@@ -52,29 +83,258 @@ portals and their purpose.
 
 Please report issues at https://gitlab.gnome.org/guidog/xdg-desktop-portal-phosh/issues.";
 
+/// Groups of the configuration file.
+const CONFIG_BACKEND: &str = "Backend";
+const CONFIG_DIALOGS: &str = "Dialogs";
+const CONFIG_INTERFACES: &str = "Interfaces";
+
+/// The interfaces to provide. Defaults come from the build configuration, can be changed from the
+/// configuration file and turned off from the command line.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct InterfaceConfig {
+    pub access: bool,
+    pub account: bool,
+    pub app_chooser: bool,
+    pub email: bool,
+    pub file_chooser: bool,
+    pub print: bool,
+    pub usb: bool,
+}
+
+impl InterfaceConfig {
+    pub fn new() -> Self {
+        InterfaceConfig {
+            access: bin_config::ACCESS,
+            account: bin_config::ACCOUNT,
+            app_chooser: bin_config::APP_CHOOSER,
+            email: bin_config::EMAIL,
+            file_chooser: bin_config::FILE_CHOOSER,
+            print: bin_config::PRINT,
+            usb: bin_config::USB,
+        }
+    }
+
+    /// Turn the interface with the given command line name on or off. Returns `false` if the name
+    /// is unknown.
+    pub fn set(&mut self, name: &str, enabled: bool) -> bool {
+        let field = match name {
+            "access" => &mut self.access,
+            "account" => &mut self.account,
+            "app-chooser" => &mut self.app_chooser,
+            "email" => &mut self.email,
+            "file-chooser" => &mut self.file_chooser,
+            "print" => &mut self.print,
+            "usb" => &mut self.usb,
+            _ => return false,
+        };
+        *field = enabled;
+        true
+    }
+
+    /// Turn off the interface with the given command line name. Returns `false` if the name is
+    /// unknown.
+    pub fn disable(&mut self, name: &str) -> bool {
+        self.set(name, false)
+    }
+
+    /// The interfaces that are turned on.
+    pub fn enabled(self) -> Vec<Interface> {
+        [
+            (self.access, Interface::Access),
+            (self.account, Interface::Account),
+            (self.app_chooser, Interface::AppChooser),
+            (self.email, Interface::Email),
+            (self.file_chooser, Interface::FileChooser),
+            (self.print, Interface::Print),
+            (self.usb, Interface::Usb),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, interface)| enabled.then_some(interface))
+        .collect()
+    }
+}
+
+/// How log messages are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    /// The `GLib` default handler, which logs structured to the journal when connected to it.
+    Default,
+    /// `LEVEL domain: message` on a single line, for scanning in a terminal.
+    Compact,
+}
+
+#[allow(clippy::struct_excessive_bools)]
 struct Options {
+    pub app_chooser_modal: Option<bool>,
+    pub dbus_name: String,
+    pub default_modal: bool,
+    pub idle_exit: Option<Duration>,
+    pub interfaces: InterfaceConfig,
+    pub log_domains: Vec<String>,
+    pub log_format: LogFormat,
+    #[cfg(feature = "mock")]
+    pub mock: bool,
+    pub quiet: bool,
     pub replace: bool,
+    pub self_test: bool,
+    pub shortcut_folders: Vec<PathBuf>,
     pub verbose: bool,
+    pub xdg_default_folders: bool,
 }
 
 impl Options {
     pub fn new() -> Self {
         Options {
+            app_chooser_modal: bin_config::APP_CHOOSER_MODAL_DEFAULT,
+            dbus_name: String::from(bin_config::DBUS_NAME),
+            default_modal: defaults::default_modal(),
+            idle_exit: None,
+            interfaces: InterfaceConfig::new(),
+            log_domains: Vec::new(),
+            log_format: LogFormat::Default,
+            #[cfg(feature = "mock")]
+            mock: false,
+            quiet: false,
             replace: false,
+            self_test: false,
+            shortcut_folders: defaults::shortcut_folders(),
             verbose: false,
+            xdg_default_folders: defaults::xdg_default_folders(),
         }
     }
+
+    /// Apply a configuration file on top of the build configuration. Unknown groups and keys are
+    /// skipped with a warning, so that a file can be shared across versions. Returns the name of the
+    /// first key with an invalid value.
+    pub fn apply_config(&mut self, key_file: &glib::KeyFile) -> Result<(), String> {
+        for group in &key_file.groups() {
+            let group = group.as_str();
+            let Ok(keys) = key_file.keys(group) else {
+                continue;
+            };
+            for key in &keys {
+                let key = key.as_str();
+                let invalid = || format!("{group}.{key}");
+                let known = match (group, key) {
+                    (CONFIG_INTERFACES, name) => {
+                        let enabled = key_file.boolean(group, key).map_err(|_| invalid())?;
+                        self.interfaces.set(name, enabled)
+                    }
+                    (CONFIG_BACKEND, "dbus-name") => {
+                        let dbus_name = key_file.string(group, key).map_err(|_| invalid())?;
+                        if dbus_name.is_empty() {
+                            return Err(invalid());
+                        }
+                        self.dbus_name = dbus_name.into();
+                        true
+                    }
+                    (CONFIG_BACKEND, "idle-exit") => match key_file.uint64(group, key) {
+                        Ok(seconds) if seconds > 0 => {
+                            self.idle_exit = Some(Duration::from_secs(seconds));
+                            true
+                        }
+                        _ => return Err(invalid()),
+                    },
+                    (CONFIG_DIALOGS, "modal") => {
+                        self.default_modal = key_file.boolean(group, key).map_err(|_| invalid())?;
+                        true
+                    }
+                    (CONFIG_DIALOGS, "app-chooser-modal") => {
+                        let modal = key_file.boolean(group, key).map_err(|_| invalid())?;
+                        self.app_chooser_modal = Some(modal);
+                        true
+                    }
+                    (CONFIG_DIALOGS, "xdg-folders") => {
+                        self.xdg_default_folders =
+                            key_file.boolean(group, key).map_err(|_| invalid())?;
+                        true
+                    }
+                    (CONFIG_DIALOGS, "shortcuts") => {
+                        let folders = key_file.string_list(group, key).map_err(|_| invalid())?;
+                        self.shortcut_folders = folders
+                            .iter()
+                            .map(|folder| PathBuf::from(folder.as_str()))
+                            .collect();
+                        true
+                    }
+                    _ => false,
+                };
+                if !known {
+                    glib::g_warning!(
+                        LOG_DOMAIN,
+                        "Ignoring unknown configuration key {group}.{key}"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What `--version` prints. The first line is the bare version, so that scripts can keep reading
+/// just that, followed by the commit and the interfaces enabled at build time.
+fn version_text() -> String {
+    let interfaces: Vec<&str> = InterfaceConfig::new()
+        .enabled()
+        .into_iter()
+        .map(Interface::name)
+        .collect();
+    format!(
+        "{}\nCommit: {}\nInterfaces: {}",
+        env!("CARGO_PKG_VERSION"),
+        env!("XDPP_COMMIT"),
+        interfaces.join(", ")
+    )
+}
+
+/// Load the file given with `--config`, if any, into `options`.
+fn load_config(options: &mut Options, args: &[String]) -> Result<(), ExitCode> {
+    let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--config=")) else {
+        return Ok(());
+    };
+    if path.is_empty() {
+        let error = gettextf("Missing value for {}", &["--config"]);
+        eprintln!("{error}");
+        return Err(ExitCode::FAILURE);
+    }
+
+    let key_file = glib::KeyFile::new();
+    if let Err(error) = key_file.load_from_file(path, glib::KeyFileFlags::NONE) {
+        let error = gettextf("Unable to read {}: {}", &[path, error.message()]);
+        eprintln!("{error}");
+        return Err(ExitCode::FAILURE);
+    }
+    if let Err(key) = options.apply_config(&key_file) {
+        let error = gettextf("Invalid value for {}", &[&key]);
+        eprintln!("{error}");
+        return Err(ExitCode::FAILURE);
+    }
+    Ok(())
 }
 
-fn handle_cli() -> Result<Options, ExitCode> {
-    let mut args = std::env::args();
+/// The value of an option given as `NAME=VALUE`, which must not be empty.
+fn option_value<'a>(arg: &'a str, name: &str) -> Result<&'a str, ExitCode> {
+    let value = arg.strip_prefix(name).unwrap_or_default();
+    if value.is_empty() {
+        let error = gettextf("Missing value for {}", &[name.trim_end_matches('=')]);
+        eprintln!("{error}");
+        return Err(ExitCode::FAILURE);
+    }
+    Ok(value)
+}
 
+fn handle_cli(mut args: impl Iterator<Item = String>) -> Result<Options, ExitCode> {
     let mut options = Options::new();
 
     let Some(name) = args.next() else {
         return Ok(options);
     };
 
+    // The configuration file goes first, so that the other arguments override it.
+    let args: Vec<String> = args.collect();
+    load_config(&mut options, &args)?;
+
     for arg in args {
         match &arg[..] {
             "-h" | "--help" => {
@@ -82,16 +342,66 @@ fn handle_cli() -> Result<Options, ExitCode> {
                 println!("{help}");
                 return Err(ExitCode::SUCCESS);
             }
+            #[cfg(feature = "mock")]
+            "--mock" => {
+                options.mock = true;
+            }
+            "-q" | "--quiet" => {
+                options.quiet = true;
+            }
             "-r" | "--replace" => {
                 options.replace = true;
             }
+            "--self-test" => {
+                options.self_test = true;
+            }
             "-v" | "--verbose" => {
                 options.verbose = true;
             }
             "--version" => {
-                println!(env!("CARGO_PKG_VERSION"));
+                println!("{}", version_text());
                 return Err(ExitCode::SUCCESS);
             }
+            arg if arg.starts_with("--config=") => {}
+            arg if arg.starts_with("--dbus-name=") => {
+                options.dbus_name = String::from(option_value(arg, "--dbus-name=")?);
+            }
+            arg if arg.starts_with("--idle-exit=") => {
+                match option_value(arg, "--idle-exit=")?.parse::<u64>() {
+                    Ok(seconds) if seconds > 0 => {
+                        options.idle_exit = Some(Duration::from_secs(seconds));
+                    }
+                    _ => {
+                        let error = gettextf("Invalid value for {}", &["--idle-exit"]);
+                        eprintln!("{error}");
+                        return Err(ExitCode::FAILURE);
+                    }
+                }
+            }
+            arg if arg.starts_with("--log-domain=") => {
+                let domain = option_value(arg, "--log-domain=")?;
+                options.log_domains.push(String::from(domain));
+            }
+            arg if arg.starts_with("--log-format=") => {
+                options.log_format = match option_value(arg, "--log-format=")? {
+                    "default" => LogFormat::Default,
+                    "compact" => LogFormat::Compact,
+                    _ => {
+                        let error = gettextf("Invalid value for {}", &["--log-format"]);
+                        eprintln!("{error}");
+                        return Err(ExitCode::FAILURE);
+                    }
+                };
+            }
+            arg if arg.starts_with("--disable=") => {
+                for name in option_value(arg, "--disable=")?.split(',') {
+                    if !options.interfaces.disable(name) {
+                        let error = gettextf("Unknown interface: {}", &[name]);
+                        eprintln!("{error}");
+                        return Err(ExitCode::FAILURE);
+                    }
+                }
+            }
             arg => {
                 let error = gettextf("Unknown argument: {}", &[arg]);
                 eprintln!("{error}");
@@ -100,36 +410,469 @@ fn handle_cli() -> Result<Options, ExitCode> {
         }
     }
 
+    if options.quiet && options.verbose {
+        let error = gettextf(
+            "Options {} and {} cannot be used together",
+            &["--quiet", "--verbose"],
+        );
+        eprintln!("{error}");
+        return Err(ExitCode::FAILURE);
+    }
+
+    // Debug messages are only printed with --verbose, so the filter would do nothing without it.
+    if !options.log_domains.is_empty() && !options.verbose {
+        let error = gettextf("Option {} requires {}", &["--log-domain", "--verbose"]);
+        eprintln!("{error}");
+        return Err(ExitCode::FAILURE);
+    }
+
     Ok(options)
 }
 
-fn message_handler(domain: Option<&str>, level: glib::LogLevel, message: &str) {
+/// Whether debug messages of `domain` should be printed. They come from our own domains, narrowed
+/// down to `log_domains` if any are given.
+fn promotes_domain(log_domains: &[String], domain: Option<&str>) -> bool {
+    let domain = domain.unwrap_or("");
+    domain.starts_with(LOG_DOMAIN)
+        && (log_domains.is_empty() || log_domains.iter().any(|log_domain| log_domain == domain))
+}
+
+/// Format a message as `LEVEL domain: message` on a single line.
+fn compact_line(domain: Option<&str>, level: glib::LogLevel, message: &str) -> String {
+    let level = match level {
+        glib::LogLevel::Error => "ERROR",
+        glib::LogLevel::Critical => "CRITICAL",
+        glib::LogLevel::Warning => "WARNING",
+        glib::LogLevel::Message => "MESSAGE",
+        glib::LogLevel::Info => "INFO",
+        glib::LogLevel::Debug => "DEBUG",
+    };
+    let message = message.replace('\n', " ");
+    match domain {
+        Some(domain) => format!("{level} {domain}: {message}"),
+        None => format!("{level} {message}"),
+    }
+}
+
+/// Whether the default handler prints a message of `level` from `domain`. Debug and info messages
+/// need their domain listed in `G_MESSAGES_DEBUG`.
+fn is_printed(domain: Option<&str>, level: glib::LogLevel) -> bool {
+    if !matches!(level, glib::LogLevel::Info | glib::LogLevel::Debug) {
+        return true;
+    }
+    std::env::var("G_MESSAGES_DEBUG").is_ok_and(|domains| {
+        domains
+            .split([' ', ','])
+            .any(|debug_domain| debug_domain == "all" || Some(debug_domain) == domain)
+    })
+}
+
+fn log_message(format: LogFormat, domain: Option<&str>, level: glib::LogLevel, message: &str) {
+    if format == LogFormat::Default {
+        glib::log_default_handler(domain, level, Some(message));
+    } else if is_printed(domain, level) {
+        eprintln!("{}", compact_line(domain, level, message));
+    }
+}
+
+fn message_handler(
+    log_domains: &[String],
+    format: LogFormat,
+    domain: Option<&str>,
+    level: glib::LogLevel,
+    message: &str,
+) {
     let mut new_level = level;
 
-    if level == glib::LogLevel::Debug && domain.unwrap_or("").starts_with(LOG_DOMAIN) {
+    if level == glib::LogLevel::Debug && promotes_domain(log_domains, domain) {
         new_level = glib::LogLevel::Message;
     }
 
-    glib::log_default_handler(domain, new_level, Some(message));
+    log_message(format, domain, new_level, message);
+}
+
+fn quiet_message_handler(
+    format: LogFormat,
+    domain: Option<&str>,
+    level: glib::LogLevel,
+    message: &str,
+) {
+    if domain.unwrap_or("").starts_with(LOG_DOMAIN)
+        && matches!(
+            level,
+            glib::LogLevel::Message | glib::LogLevel::Info | glib::LogLevel::Debug
+        )
+    {
+        return;
+    }
+
+    log_message(format, domain, level, message);
+}
+
+/// Build the responder of `interface`. Fails for a broken install that lacks the UI resources of
+/// the responder, which would otherwise panic inside GTK while binding the template.
+fn new_responder(interface: Interface) -> Result<Box<dyn Responder>, XdppError> {
+    if let Some(path) = responders::missing_template(interface) {
+        glib::g_critical!(
+            LOG_DOMAIN,
+            "Resource {path} for {} is missing, check the installation",
+            interface.name()
+        );
+        return Err(XdppError::Internal(format!("Missing resource {path}")));
+    }
+
+    let responder: Box<dyn Responder> = match interface {
+        Interface::Access => Box::new(responders::AccessWindow::new()),
+        Interface::Account => Box::new(responders::AccountWindow::new()),
+        Interface::AppChooser => Box::new(responders::AppChooserWindow::new()),
+        Interface::Email => Box::new(responders::Email::new()),
+        Interface::FileChooser => Box::new(responders::FileChooser::new()),
+        Interface::Print => Box::new(responders::Print::new()),
+        Interface::Usb => Box::new(responders::UsbWindow::new()),
+    };
+    Ok(responder)
+}
+
+/// Build a responder of each interface, so that a broken install, like one missing the UI
+/// resources, shows up before the first request does. Returns whether all of them could be built.
+fn self_test(interfaces: &[Interface]) -> bool {
+    let mut passed = true;
+    for &interface in interfaces {
+        // A missing template is reported by `new_responder`. A panic while binding one happens
+        // inside GObject's instance init, where it aborts instead of unwinding, so it can not be
+        // caught here.
+        if new_responder(interface).is_ok() {
+            glib::g_message!(LOG_DOMAIN, "Self-test of {} passed", interface.name());
+        } else {
+            glib::g_critical!(LOG_DOMAIN, "Self-test of {} failed", interface.name());
+            passed = false;
+        }
+    }
+    passed
+}
+
+/// Build the responder for a new request of the given interface.
+type ResponderFactory = fn(Interface) -> Result<Box<dyn Responder>, XdppError>;
+
+/// Pick how responders are built. In mock mode, every request gets a canned reply.
+#[cfg(feature = "mock")]
+fn responder_factory(mock: bool) -> ResponderFactory {
+    if mock {
+        |_| Ok(Box::new(responders::MockResponder))
+    } else {
+        new_responder
+    }
+}
+
+#[cfg(not(feature = "mock"))]
+fn responder_factory(_mock: bool) -> ResponderFactory {
+    new_responder
+}
+
+/// A responder along with what is known about the request it handles.
+struct ActiveRequest {
+    responder: Box<dyn Responder>,
+    info: RequestInfo,
+    /// Kept for its `Drop`, which stops the timer once the request is gone.
+    _watchdog: Option<Watchdog>,
+}
+
+/// Warns once if a request stays open for long, to find the dialogs users get stuck in. Unlike a
+/// timeout, it leaves the request alone.
+struct Watchdog {
+    source: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl Watchdog {
+    fn start(timeout: Duration, info: &RequestInfo) -> Self {
+        let source = Rc::new(RefCell::new(None));
+        let message = format!(
+            "Request {} of {} by {} is still open after {} seconds",
+            info.request_id,
+            info.interface,
+            info.app_id.as_deref().unwrap_or("an unknown app"),
+            timeout.as_secs()
+        );
+        let id = glib::timeout_add_local_once(
+            timeout,
+            glib::clone!(
+                #[weak]
+                source,
+                move || {
+                    source.borrow_mut().take();
+                    glib::g_warning!(LOG_DOMAIN, "{message}");
+                }
+            ),
+        );
+        *source.borrow_mut() = Some(id);
+        Self { source }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        if let Some(source) = self.source.borrow_mut().take() {
+            source.remove();
+        }
+    }
+}
+
+/// Hand `request` to `responder`, containing a panic so that the other requests keep being served.
+/// The panic drops the sender of the request, which the requester then replies to as failed.
+/// Returns whether the responder is still usable.
+fn respond(responder: &dyn Responder, request: Request) -> bool {
+    let interface = request.interface();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| responder.respond(request)));
+    if result.is_err() {
+        glib::g_critical!(LOG_DOMAIN, "Responder for {} panicked", interface.name());
+        responder.cancel();
+    }
+    result.is_ok()
+}
+
+fn handle_message(
+    map: &mut HashMap<usize, ActiveRequest>,
+    factory: ResponderFactory,
+    message: Message,
+) {
+    glib::g_debug!(LOG_DOMAIN, "New message: {message:#?}");
+    match message {
+        Message::Cancel { request_id } => {
+            if let Some(active) = map.remove(&request_id) {
+                active.responder.cancel();
+            } else {
+                glib::g_critical!(LOG_DOMAIN, "No responder found for {request_id}");
+            }
+        }
+        Message::Done { request_id } => {
+            map.remove(&request_id);
+        }
+        Message::NameLost => {
+            for (_, active) in map.drain() {
+                active.responder.cancel();
+            }
+        }
+        Message::CancelApp { app_id } => {
+            let request_ids: Vec<usize> = map
+                .iter()
+                .filter(|(_, active)| active.info.app_id.as_ref() == Some(&app_id))
+                .map(|(request_id, _)| *request_id)
+                .collect();
+            glib::g_debug!(
+                LOG_DOMAIN,
+                "Cancelling {} requests of {app_id}",
+                request_ids.len()
+            );
+            for request_id in request_ids {
+                if let Some(active) = map.remove(&request_id) {
+                    active.responder.cancel();
+                }
+            }
+        }
+        Message::ListRequests { sender } => {
+            let mut requests: Vec<RequestInfo> =
+                map.values().map(|active| active.info.clone()).collect();
+            requests.sort_by_key(|info| info.request_id);
+            if sender.send(requests).is_err() {
+                glib::g_critical!(LOG_DOMAIN, "Unable to send response through sender");
+            }
+        }
+        Message::Request {
+            request_id,
+            request,
+        } => match request.route() {
+            Route::New => {
+                let interface = request.interface();
+                let info = RequestInfo {
+                    request_id,
+                    interface: interface.name(),
+                    app_id: request
+                        .application()
+                        .and_then(|application| application.app_id.as_ref())
+                        .map(ToString::to_string),
+                };
+                match factory(interface) {
+                    Ok(responder) => {
+                        if respond(responder.as_ref(), request) {
+                            let watchdog = Watchdog::start(WATCHDOG_TIMEOUT, &info);
+                            let active = ActiveRequest {
+                                responder,
+                                info,
+                                _watchdog: Some(watchdog),
+                            };
+                            map.insert(request_id, active);
+                        }
+                    }
+                    Err(error) => request.reply_error(error.into()),
+                }
+            }
+            Route::Existing => match map.get(&request_id) {
+                Some(active) if active.info.interface == request.interface().name() => {
+                    if !respond(active.responder.as_ref(), request) {
+                        map.remove(&request_id);
+                    }
+                }
+                _ => {
+                    glib::g_critical!(LOG_DOMAIN, "No responder found for {request_id}");
+                    let error =
+                        XdppError::ResponderMissing(String::from("No active request to update"));
+                    request.reply_error(error.into());
+                }
+            },
+        },
+    }
+}
+
+/// Reply to the requests still queued when the backend stops and cancel the active ones, so that no
+/// app is left waiting for a reply that never comes.
+fn drain_messages(receiver: &mut mpsc::Receiver<Message>, map: &mut HashMap<usize, ActiveRequest>) {
+    receiver.close();
+    while let Ok(message) = receiver.try_recv() {
+        if let Message::Request {
+            request_id,
+            request,
+        } = message
+        {
+            glib::g_debug!(LOG_DOMAIN, "Failing pending request {request_id}");
+            let error = XdppError::ChannelClosed(String::from("The backend is shutting down"));
+            request.reply_error(error.into());
+        }
+    }
+    for (_, active) in map.drain() {
+        active.responder.cancel();
+    }
+}
+
+/// Quits the main loop once the backend was idle for a while, so that D-Bus activation can start it
+/// again on demand. An open window, like a notice outliving its request, keeps it running.
+struct IdleExit {
+    timeout: Duration,
+    main_loop: glib::MainLoop,
+    source: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl IdleExit {
+    fn new(timeout: Duration, main_loop: glib::MainLoop) -> Self {
+        Self {
+            timeout,
+            main_loop,
+            source: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Restart the timer if the backend is idle, stop it otherwise.
+    fn reset(&self, idle: bool) {
+        if let Some(source) = self.source.borrow_mut().take() {
+            source.remove();
+        }
+        if !idle {
+            return;
+        }
+
+        let timeout = self.timeout;
+        let source = glib::timeout_add_local(
+            timeout,
+            glib::clone!(
+                #[strong(rename_to = main_loop)]
+                self.main_loop,
+                #[weak(rename_to = source)]
+                self.source,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || {
+                    if has_open_windows() {
+                        return glib::ControlFlow::Continue;
+                    }
+                    glib::g_message!(
+                        LOG_DOMAIN,
+                        "Exiting after {} seconds without requests",
+                        timeout.as_secs()
+                    );
+                    source.borrow_mut().take();
+                    main_loop.quit();
+                    glib::ControlFlow::Break
+                }
+            ),
+        );
+        *self.source.borrow_mut() = Some(source);
+    }
+}
+
+fn has_open_windows() -> bool {
+    gtk::is_initialized()
+        && gtk::Window::list_toplevels()
+            .iter()
+            .any(gtk::prelude::WidgetExt::is_visible)
 }
 
 fn main() -> ExitCode {
-    xdg_desktop_portal_phosh::i18n_init();
+    if let Err(error) = xdg_desktop_portal_phosh::i18n_init() {
+        eprintln!("{error}");
+        return ExitCode::FAILURE;
+    }
 
-    let options = match handle_cli() {
+    let options = match handle_cli(std::env::args()) {
         Ok(options) => options,
         Err(code) => return code,
     };
 
+    // The journal keeps the structured output of the default handler.
+    let log_format = if glib::log_writer_is_journald(std::io::stderr()) {
+        LogFormat::Default
+    } else {
+        options.log_format
+    };
     if options.verbose {
-        glib::log_set_default_handler(message_handler);
+        let log_domains = options.log_domains.clone();
+        glib::log_set_default_handler(move |domain, level, message| {
+            message_handler(&log_domains, log_format, domain, level, message);
+        });
+    } else if options.quiet {
+        glib::log_set_default_handler(move |domain, level, message| {
+            quiet_message_handler(log_format, domain, level, message);
+        });
+    } else if log_format == LogFormat::Compact {
+        glib::log_set_default_handler(move |domain, level, message| {
+            log_message(log_format, domain, level, message);
+        });
+    }
+
+    defaults::set_default_modal(options.default_modal);
+    defaults::set_app_chooser_modal(options.app_chooser_modal);
+    defaults::set_xdg_default_folders(options.xdg_default_folders);
+    defaults::set_shortcut_folders(options.shortcut_folders.clone());
+
+    #[cfg(feature = "mock")]
+    let mock = options.mock;
+    #[cfg(not(feature = "mock"))]
+    let mock = false;
+
+    // Mock responders show no windows, so they can run without a display. The self-test always
+    // builds the real ones.
+    if mock && !options.self_test {
+        glib::g_message!(LOG_DOMAIN, "Replying to requests with canned results");
+    } else if let Err(error) = xdg_desktop_portal_phosh::init() {
+        glib::g_critical!(LOG_DOMAIN, "Initialization failed: {error}");
+        return ExitCode::FAILURE;
+    }
+
+    if options.self_test {
+        return if self_test(&options.interfaces.enabled()) {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        };
     }
 
-    xdg_desktop_portal_phosh::init();
+    let factory = responder_factory(mock);
 
     let main_loop = glib::MainLoop::new(None, false);
 
-    let (sender, mut receiver) = mpsc::channel(bin_config::MPSC_BUFFER);
+    let (sender, receiver) = mpsc::channel(bin_config::MPSC_BUFFER);
+
+    let idle_exit = options
+        .idle_exit
+        .map(|timeout| IdleExit::new(timeout, main_loop.clone()));
 
     let runtime = Runtime::new().unwrap();
     runtime.spawn(glib::clone!(
@@ -142,95 +885,75 @@ fn main() -> ExitCode {
         }
     ));
 
-    let mut map: HashMap<usize, Box<dyn Responder>> = HashMap::new();
-    glib::spawn_future_local(async move {
-        while let Some(message) = receiver.recv().await {
-            glib::g_debug!(LOG_DOMAIN, "New message: {message:#?}");
-            match message {
-                Message::Cancel { request_id } => {
-                    if let Some(responder) = map.remove(&request_id) {
-                        responder.cancel();
-                    } else {
-                        glib::g_critical!(LOG_DOMAIN, "No responder found for {request_id}");
-                    }
-                }
-                Message::Done { request_id } => {
-                    map.remove(&request_id);
+    // Shared with the dispatch loop, so that whatever is left can be drained once it stops.
+    let receiver = Rc::new(RefCell::new(receiver));
+    let map: Rc<RefCell<HashMap<usize, ActiveRequest>>> = Rc::new(RefCell::new(HashMap::new()));
+    glib::spawn_future_local(glib::clone!(
+        #[strong]
+        main_loop,
+        #[strong]
+        receiver,
+        #[strong]
+        map,
+        async move {
+            if let Some(idle_exit) = &idle_exit {
+                idle_exit.reset(true);
+            }
+            while let Some(message) =
+                std::future::poll_fn(|cx| receiver.borrow_mut().poll_recv(cx)).await
+            {
+                let name_lost = matches!(message, Message::NameLost);
+                handle_message(&mut map.borrow_mut(), factory, message);
+                if name_lost {
+                    glib::g_message!(LOG_DOMAIN, "Replaced by another instance, quitting");
+                    main_loop.quit();
+                    break;
                 }
-                Message::Request {
-                    request_id,
-                    request,
-                } => {
-                    let responder: Option<Box<dyn Responder>> = match request {
-                        Request::AccountGetUserInformation {
-                            application: _,
-                            options: _,
-                            sender: _,
-                        } => Some(Box::new(responders::AccountWindow::new())),
-                        Request::AppChooserChooseApplication {
-                            application: _,
-                            choices: _,
-                            options: _,
-                            sender: _,
-                        } => Some(Box::new(responders::AppChooserWindow::new())),
-                        Request::AppChooserUpdateChoices {
-                            choices: _,
-                            sender: _,
-                        } => {
-                            let responder = map.remove(&request_id);
-                            if responder.is_none() {
-                                glib::g_critical!(
-                                    LOG_DOMAIN,
-                                    "No responder found for {request_id}"
-                                );
-                            }
-                            responder
-                        }
-                        Request::FileChooserOpenFile {
-                            application: _,
-                            title: _,
-                            options: _,
-                            sender: _,
-                        }
-                        | Request::FileChooserSaveFile {
-                            application: _,
-                            title: _,
-                            options: _,
-                            sender: _,
-                        }
-                        | Request::FileChooserSaveFiles {
-                            application: _,
-                            title: _,
-                            options: _,
-                            sender: _,
-                        } => Some(Box::new(responders::FileChooser::new())),
-                    };
-
-                    if let Some(responder) = responder {
-                        responder.respond(request);
-                        map.insert(request_id, responder);
-                    }
+                if let Some(idle_exit) = &idle_exit {
+                    idle_exit.reset(map.borrow().is_empty());
                 }
             }
         }
-    });
+    ));
 
     glib::g_message!(LOG_DOMAIN, "Running main loop");
 
     main_loop.run();
 
+    drain_messages(&mut receiver.borrow_mut(), &mut map.borrow_mut());
+
+    // Dropping the runtime closes the connection, which releases the D-Bus name.
+    runtime.shutdown_timeout(Duration::from_secs(1));
+
     ExitCode::SUCCESS
 }
 
-async fn ashpd_main(options: &Options, sender: mpsc::Sender<Message>, main_loop: glib::MainLoop) {
-    let mut builder = match ashpd::backend::Builder::new(bin_config::DBUS_NAME) {
-        Ok(builder) => builder,
+async fn serve_debug(dbus_name: String, sender: mpsc::Sender<Message>) {
+    let name = format!("{dbus_name}.Debug");
+    let builder = zbus::connection::Builder::session()
+        .and_then(|builder| builder.name(name.as_str()))
+        .and_then(|builder| builder.serve_at(Debug::OBJECT_PATH, Debug::new(sender)));
+    let connection = match builder {
+        Ok(builder) => builder.build().await,
+        Err(error) => Err(error),
+    };
+
+    match connection {
+        Ok(_connection) => {
+            glib::g_message!(LOG_DOMAIN, "Serving debug interface under {name}");
+            std::future::pending::<()>().await;
+        }
         Err(error) => {
-            glib::g_critical!(LOG_DOMAIN, "ashpd server failed: {error}");
-            main_loop.quit();
-            return;
+            glib::g_warning!(LOG_DOMAIN, "Unable to serve debug interface: {error}");
         }
-    };
+    }
+}
+
+fn new_builder(
+    options: &Options,
+    sender: &mpsc::Sender<Message>,
+) -> ashpd::zbus::Result<ashpd::backend::Builder> {
+    let mut builder = ashpd::backend::Builder::new(options.dbus_name.as_str())?;
     let mut flags = RequestNameFlags::AllowReplacement | RequestNameFlags::DoNotQueue;
 
     if options.replace {
@@ -239,35 +962,670 @@ async fn ashpd_main(options: &Options, sender: mpsc::Sender<Message>, main_loop:
     }
     builder = builder.with_flags(flags);
 
-    builder = if bin_config::ACCOUNT {
+    builder = if options.interfaces.access {
+        glib::g_debug!(LOG_DOMAIN, "Adding interface: Access");
+        builder.access(requesters::Access::new(sender.clone()))
+    } else {
+        builder
+    };
+
+    builder = if options.interfaces.account {
         glib::g_debug!(LOG_DOMAIN, "Adding interface: Account");
         builder.account(requesters::Account::new(sender.clone()))
     } else {
         builder
     };
 
-    builder = if bin_config::APP_CHOOSER {
+    builder = if options.interfaces.app_chooser {
         glib::g_debug!(LOG_DOMAIN, "Adding interface: AppChooser");
         builder.app_chooser(requesters::AppChooser::new(sender.clone()))
     } else {
         builder
     };
 
-    builder = if bin_config::FILE_CHOOSER {
+    builder = if options.interfaces.email {
+        glib::g_debug!(LOG_DOMAIN, "Adding interface: Email");
+        builder.email(requesters::Email::new(sender.clone()))
+    } else {
+        builder
+    };
+
+    builder = if options.interfaces.file_chooser {
         glib::g_debug!(LOG_DOMAIN, "Add interface: FileChooser");
         builder.file_chooser(requesters::FileChooser::new(sender.clone()))
     } else {
         builder
     };
 
+    builder = if options.interfaces.print {
+        glib::g_debug!(LOG_DOMAIN, "Adding interface: Print");
+        builder.print(requesters::Print::new(sender.clone()))
+    } else {
+        builder
+    };
+
+    builder = if options.interfaces.usb {
+        glib::g_debug!(LOG_DOMAIN, "Adding interface: Usb");
+        builder.usb(requesters::Usb::new(sender.clone()))
+    } else {
+        builder
+    };
+
+    Ok(builder)
+}
+
+/// Look up the versions of the frontend interfaces that route to `interfaces`, and warn about the
+/// ones the frontend lacks, as their requests would never arrive. Returns a summary of the
+/// versions, or `None` if the frontend could not be asked. A frontend that is not running is left
+/// alone, so that it is not activated just for this.
+async fn check_frontend(interfaces: &[Interface]) -> Option<String> {
+    let connection = ashpd::zbus::Connection::session().await.ok()?;
+    let dbus = ashpd::zbus::fdo::DBusProxy::new(&connection).await.ok()?;
+    let frontend = BusName::try_from(FRONTEND_NAME).ok()?;
+    if !dbus.name_has_owner(frontend.clone()).await.unwrap_or(false) {
+        glib::g_debug!(
+            LOG_DOMAIN,
+            "Frontend is not running, skipping version check"
+        );
+        return None;
+    }
+
+    let properties = ashpd::zbus::fdo::PropertiesProxy::builder(&connection)
+        .destination(frontend)
+        .ok()?
+        .path(FRONTEND_PATH)
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+
+    let mut versions = Vec::new();
+    let mut missing = Vec::new();
+    for interface in interfaces {
+        let name = InterfaceName::from_static_str_unchecked(interface.frontend_name());
+        match properties.get(name, "version").await {
+            Ok(version) => {
+                let version = u32::try_from(version).unwrap_or_default();
+                versions.push(format!("{} {version}", interface.frontend_name()));
+            }
+            Err(error) => {
+                glib::g_debug!(
+                    LOG_DOMAIN,
+                    "Unable to get version of {}: {error}",
+                    interface.frontend_name()
+                );
+                missing.push(interface.frontend_name());
+            }
+        }
+    }
+
+    if !missing.is_empty() {
+        glib::g_warning!(
+            LOG_DOMAIN,
+            "The portal frontend does not provide {}, so their requests will not arrive",
+            missing.join(", ")
+        );
+    }
+
+    Some(versions.join(", "))
+}
+
+/// Wait until another instance takes over the name, like when started with `--replace`. Requests
+/// no longer reach this instance then, so the `GLib` world is told to cancel its responders and stop.
+async fn watch_name(dbus_name: &str, sender: &mpsc::Sender<Message>) {
+    let lost = async {
+        let connection = ashpd::zbus::Connection::session().await?;
+        let dbus = ashpd::zbus::fdo::DBusProxy::new(&connection).await?;
+        let owner = dbus.get_name_owner(BusName::try_from(dbus_name)?).await?;
+        let mut changes = dbus
+            .receive_name_owner_changed_with_args(&[(0, dbus_name)])
+            .await?;
+        while let Some(signal) =
+            std::future::poll_fn(|cx| Pin::new(&mut changes).poll_next(cx)).await
+        {
+            if signal.args()?.new_owner().as_ref() != Some(&owner) {
+                return Ok(true);
+            }
+        }
+        Ok::<bool, ashpd::zbus::Error>(false)
+    };
+
+    match lost.await {
+        Ok(true) => {
+            if let Err(error) = sender.send(Message::NameLost).await {
+                glib::g_critical!(LOG_DOMAIN, "Error: {error}");
+            }
+        }
+        Ok(false) => {}
+        Err(error) => glib::g_warning!(LOG_DOMAIN, "Unable to watch name {dbus_name}: {error}"),
+    }
+}
+
+async fn ashpd_main(options: &Options, sender: mpsc::Sender<Message>, main_loop: glib::MainLoop) {
+    if options.verbose {
+        tokio::spawn(serve_debug(options.dbus_name.clone(), sender.clone()));
+    }
+
+    let frontend = check_frontend(&options.interfaces.enabled()).await;
     glib::g_message!(
         LOG_DOMAIN,
-        "Running ashpd loop under {}",
-        bin_config::DBUS_NAME
+        "Running ashpd loop under {}, frontend: {}",
+        options.dbus_name,
+        frontend.as_deref().unwrap_or("unknown")
     );
 
-    if let Err(error) = builder.build().await {
-        glib::g_critical!(LOG_DOMAIN, "ashpd server failed: {error}");
-        main_loop.quit();
+    // The previous instance may still own the name for a moment when restarted. With `--replace`,
+    // we take the name over right away, so there is nothing to wait for.
+    let mut delay = NAME_RETRY_DELAY;
+    for attempt in 1..=NAME_ATTEMPTS {
+        let builder = match new_builder(options, &sender) {
+            Ok(builder) => builder,
+            Err(error) => {
+                glib::g_critical!(LOG_DOMAIN, "ashpd server failed: {error}");
+                main_loop.quit();
+                return;
+            }
+        };
+
+        glib::g_debug!(
+            LOG_DOMAIN,
+            "Acquiring name {} (attempt {attempt} of {NAME_ATTEMPTS})",
+            options.dbus_name
+        );
+        match builder.build().await {
+            Ok(()) => {
+                watch_name(&options.dbus_name, &sender).await;
+                return;
+            }
+            Err(PortalError::ZBus(ashpd::zbus::Error::NameTaken))
+                if !options.replace && attempt < NAME_ATTEMPTS =>
+            {
+                glib::g_debug!(LOG_DOMAIN, "Name is taken, retrying in {delay:?}");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            Err(error) => {
+                glib::g_critical!(LOG_DOMAIN, "ashpd server failed: {error}");
+                main_loop.quit();
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    struct NullResponder;
+
+    impl Responder for NullResponder {
+        fn respond(&self, _request: Request) {}
+        fn cancel(&self) {}
+    }
+
+    /// Counts the requests it was handed.
+    struct CountingResponder(Rc<Cell<usize>>);
+
+    impl Responder for CountingResponder {
+        fn respond(&self, _request: Request) {
+            self.0.set(self.0.get() + 1);
+        }
+        fn cancel(&self) {}
+    }
+
+    /// Remembers whether it was cancelled.
+    struct CancelledResponder(Rc<Cell<bool>>);
+
+    impl Responder for CancelledResponder {
+        fn respond(&self, _request: Request) {}
+        fn cancel(&self) {
+            self.0.set(true);
+        }
+    }
+
+    struct PanickingResponder;
+
+    impl Responder for PanickingResponder {
+        fn respond(&self, _request: Request) {
+            panic!("Malformed request");
+        }
+        fn cancel(&self) {}
+    }
+
+    fn parse(args: &[&str]) -> Result<Options, ExitCode> {
+        handle_cli(args.iter().map(|arg| String::from(*arg)))
+    }
+
+    #[test]
+    fn test_interfaces_default() {
+        let options = parse(&["xdpp"]).ok().unwrap();
+        assert_eq!(options.interfaces, InterfaceConfig::new());
+    }
+
+    #[test]
+    fn test_interfaces_disable() {
+        let options = parse(&["xdpp", "--disable=account,usb"]).ok().unwrap();
+        let mut expected = InterfaceConfig::new();
+        expected.account = false;
+        expected.usb = false;
+        assert_eq!(options.interfaces, expected);
+        assert_eq!(
+            options.interfaces.enabled(),
+            vec![
+                Interface::Access,
+                Interface::AppChooser,
+                Interface::Email,
+                Interface::FileChooser,
+                Interface::Print
+            ]
+        );
+
+        assert!(parse(&["xdpp", "--disable=camera"]).is_err());
+    }
+
+    #[test]
+    fn test_self_test() {
+        assert!(!parse(&["xdpp"]).ok().unwrap().self_test);
+        let options = parse(&["xdpp", "--self-test", "--disable=usb"])
+            .ok()
+            .unwrap();
+        assert!(options.self_test);
+        assert!(!options.interfaces.enabled().contains(&Interface::Usb));
+    }
+
+    #[test]
+    fn test_idle_exit() {
+        let options = parse(&["xdpp", "--idle-exit=30"]).ok().unwrap();
+        assert_eq!(options.idle_exit, Some(Duration::from_secs(30)));
+        assert_eq!(parse(&["xdpp"]).ok().unwrap().idle_exit, None);
+
+        assert!(parse(&["xdpp", "--idle-exit=0"]).is_err());
+        assert!(parse(&["xdpp", "--idle-exit=soon"]).is_err());
+    }
+
+    #[test]
+    fn test_config() {
+        let directory = glib::mkdtemp(glib::tmp_dir().join("xdpp-test-XXXXXX")).unwrap();
+        let path = directory.join("config");
+        std::fs::write(
+            &path,
+            "[Interfaces]\naccount=false\nprint=true\ncamera=false\n\n\
+             [Backend]\ndbus-name=org.example.Portal\nidle-exit=30\n\n\
+             [Dialogs]\nmodal=false\napp-chooser-modal=true\nshortcuts=/srv/photos;/media/card;\n\n\
+             [Unknown]\nkey=value\n",
+        )
+        .unwrap();
+        let config = format!("--config={}", path.display());
+
+        let options = parse(&["xdpp", &config, "--idle-exit=45", "--disable=print"])
+            .ok()
+            .unwrap();
+        // The command line wins over the file, which wins over the build configuration.
+        assert_eq!(options.idle_exit, Some(Duration::from_secs(45)));
+        assert!(!options.interfaces.print);
+        assert_eq!(options.dbus_name, "org.example.Portal");
+        assert!(!options.interfaces.account);
+        assert!(!options.default_modal);
+        assert_eq!(options.app_chooser_modal, Some(true));
+        assert_eq!(
+            options.shortcut_folders,
+            [PathBuf::from("/srv/photos"), PathBuf::from("/media/card")]
+        );
+        assert_eq!(options.interfaces.usb, InterfaceConfig::new().usb);
+        assert_eq!(
+            options.xdg_default_folders,
+            Options::new().xdg_default_folders
+        );
+
+        std::fs::write(&path, "[Backend]\nidle-exit=soon\n").unwrap();
+        assert!(parse(&["xdpp", &config]).is_err());
+        assert!(parse(&["xdpp", "--config=/nonexistent/xdpp/config"]).is_err());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_log_domains() {
+        let options = parse(&[
+            "xdpp",
+            "--verbose",
+            "--log-domain=xdpp-file-chooser",
+            "--log-domain=xdpp",
+        ])
+        .ok()
+        .unwrap();
+        assert_eq!(options.log_domains, vec!["xdpp-file-chooser", "xdpp"]);
+        assert!(promotes_domain(
+            &options.log_domains,
+            Some("xdpp-file-chooser")
+        ));
+        assert!(!promotes_domain(&options.log_domains, Some("xdpp-print")));
+        assert!(promotes_domain(&[], Some("xdpp-print")));
+        assert!(!promotes_domain(&[], Some("Gtk")));
+
+        assert!(parse(&["xdpp", "--verbose", "--log-domain="]).is_err());
+        assert!(parse(&["xdpp", "--log-domain=xdpp"]).is_err());
+    }
+
+    #[test]
+    fn test_version_text() {
+        let text = version_text();
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some(env!("CARGO_PKG_VERSION")));
+        assert!(lines.next().unwrap().starts_with("Commit: "));
+        let interfaces = lines.next().unwrap();
+        for interface in InterfaceConfig::new().enabled() {
+            assert!(interfaces.contains(interface.name()));
+        }
+    }
+
+    #[test]
+    fn test_log_format() {
+        assert_eq!(
+            parse(&["xdpp"]).ok().unwrap().log_format,
+            LogFormat::Default
+        );
+        let options = parse(&["xdpp", "--log-format=compact"]).ok().unwrap();
+        assert_eq!(options.log_format, LogFormat::Compact);
+        assert!(parse(&["xdpp", "--log-format=json"]).is_err());
+
+        assert_eq!(
+            compact_line(Some("xdpp-print"), glib::LogLevel::Warning, "Job\nfailed"),
+            "WARNING xdpp-print: Job failed"
+        );
+        assert_eq!(
+            compact_line(None, glib::LogLevel::Debug, "Ready"),
+            "DEBUG Ready"
+        );
+    }
+
+    #[test]
+    fn test_name_lost() {
+        let mut map = HashMap::new();
+        for request_id in [1, 2] {
+            let info = RequestInfo {
+                request_id,
+                interface: Interface::Print.name(),
+                app_id: None,
+            };
+            let responder = Box::new(NullResponder);
+            map.insert(
+                request_id,
+                ActiveRequest {
+                    responder,
+                    info,
+                    _watchdog: None,
+                },
+            );
+        }
+
+        handle_message(&mut map, new_responder, Message::NameLost);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_cancel_app() {
+        let mut map = HashMap::new();
+        let mut cancelled = Vec::new();
+        for (request_id, app_id) in [
+            (1, "mobi.phosh.First"),
+            (2, "mobi.phosh.First"),
+            (3, "mobi.phosh.Second"),
+        ] {
+            let flag = Rc::new(Cell::new(false));
+            let info = RequestInfo {
+                request_id,
+                interface: Interface::Print.name(),
+                app_id: Some(String::from(app_id)),
+            };
+            let responder = Box::new(CancelledResponder(flag.clone()));
+            map.insert(
+                request_id,
+                ActiveRequest {
+                    responder,
+                    info,
+                    _watchdog: None,
+                },
+            );
+            cancelled.push(flag);
+        }
+
+        let app_id = String::from("mobi.phosh.First");
+        handle_message(&mut map, new_responder, Message::CancelApp { app_id });
+        assert!(cancelled[0].get());
+        assert!(cancelled[1].get());
+        assert!(!cancelled[2].get());
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![&3]);
+    }
+
+    #[test]
+    fn test_list_requests() {
+        let info = RequestInfo {
+            request_id: 7,
+            interface: "org.freedesktop.impl.portal.Print",
+            app_id: None,
+        };
+        let mut map = HashMap::new();
+        map.insert(
+            7,
+            ActiveRequest {
+                responder: Box::new(NullResponder),
+                info: info.clone(),
+                _watchdog: None,
+            },
+        );
+
+        let (sender, mut receiver) = oneshot::channel();
+        handle_message(&mut map, new_responder, Message::ListRequests { sender });
+        assert_eq!(receiver.try_recv().unwrap(), vec![info]);
+    }
+
+    #[test]
+    fn test_route_update_to_existing() {
+        let responded = Rc::new(Cell::new(0));
+        let mut map = HashMap::new();
+        map.insert(
+            3,
+            ActiveRequest {
+                responder: Box::new(CountingResponder(responded.clone())),
+                info: RequestInfo {
+                    request_id: 3,
+                    interface: Interface::AppChooser.name(),
+                    app_id: None,
+                },
+                _watchdog: None,
+            },
+        );
+
+        let (sender, _receiver) = oneshot::channel();
+        let request = Request::AppChooserUpdateChoices {
+            choices: Vec::new(),
+            sender,
+        };
+        handle_message(
+            &mut map,
+            new_responder,
+            Message::request_with_id(3, request),
+        );
+        assert_eq!(responded.get(), 1);
+        assert!(map.contains_key(&3));
+
+        let (sender, mut receiver) = oneshot::channel();
+        let request = Request::AppChooserUpdateChoices {
+            choices: Vec::new(),
+            sender,
+        };
+        handle_message(
+            &mut map,
+            new_responder,
+            Message::request_with_id(4, request),
+        );
+        assert!(receiver.try_recv().unwrap().is_err());
+        assert_eq!(responded.get(), 1);
+    }
+
+    #[test]
+    fn test_missing_template() {
+        let mut map = HashMap::new();
+        let (sender, mut receiver) = oneshot::channel();
+        let request = Request::AccessAccessDialog {
+            application: xdg_desktop_portal_phosh::Application {
+                app_id: None,
+                window_identifier: None,
+            },
+            title: String::from("Allow Camera?"),
+            subtitle: String::new(),
+            body: String::new(),
+            modal: None,
+            deny_label: None,
+            grant_label: None,
+            icon_name: None,
+            choices: Vec::new(),
+            camera: false,
+            decision_key: None,
+            sender,
+        };
+        handle_message(
+            &mut map,
+            |_| Err(XdppError::Internal(String::from("Missing resource"))),
+            Message::request_with_id(1, request),
+        );
+        assert!(matches!(
+            receiver.try_recv().unwrap(),
+            Err(PortalError::Failed(_))
+        ));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_new_responder() {
+        gtk::test_synced(|| {
+            xdg_desktop_portal_phosh::init().unwrap();
+            for interface in [
+                Interface::Access,
+                Interface::Account,
+                Interface::AppChooser,
+                Interface::Email,
+                Interface::FileChooser,
+                Interface::Print,
+                Interface::Usb,
+            ] {
+                assert!(new_responder(interface).is_ok(), "{}", interface.name());
+            }
+        });
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let info = RequestInfo {
+            request_id: 1,
+            interface: Interface::Print.name(),
+            app_id: Some(String::from("mobi.phosh.Test")),
+        };
+        let context = glib::MainContext::default();
+        let _acquire = context.acquire().unwrap();
+
+        let cancelled = Watchdog::start(Duration::from_millis(10), &info);
+        drop(cancelled);
+
+        let watchdog = Watchdog::start(Duration::from_millis(10), &info);
+        assert!(watchdog.source.borrow().is_some());
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while watchdog.source.borrow().is_some() && std::time::Instant::now() < deadline {
+            context.iteration(true);
+        }
+        assert!(watchdog.source.borrow().is_none());
+    }
+
+    #[test]
+    fn test_responder_panic() {
+        let responded = Rc::new(Cell::new(0));
+        let mut map = HashMap::new();
+        for (request_id, responder) in [
+            (3, Box::new(PanickingResponder) as Box<dyn Responder>),
+            (5, Box::new(CountingResponder(responded.clone()))),
+        ] {
+            let info = RequestInfo {
+                request_id,
+                interface: Interface::AppChooser.name(),
+                app_id: None,
+            };
+            map.insert(
+                request_id,
+                ActiveRequest {
+                    responder,
+                    info,
+                    _watchdog: None,
+                },
+            );
+        }
+
+        let (sender, mut receiver) = oneshot::channel();
+        let request = Request::AppChooserUpdateChoices {
+            choices: Vec::new(),
+            sender,
+        };
+        handle_message(
+            &mut map,
+            new_responder,
+            Message::request_with_id(3, request),
+        );
+        assert!(receiver.try_recv().is_err());
+        assert!(!map.contains_key(&3));
+
+        let (sender, _receiver) = oneshot::channel();
+        let request = Request::AppChooserUpdateChoices {
+            choices: Vec::new(),
+            sender,
+        };
+        handle_message(
+            &mut map,
+            new_responder,
+            Message::request_with_id(5, request),
+        );
+        assert_eq!(responded.get(), 1);
+    }
+
+    #[test]
+    fn test_drain_messages() {
+        let cancelled = Rc::new(Cell::new(false));
+        let mut map = HashMap::new();
+        map.insert(
+            1,
+            ActiveRequest {
+                responder: Box::new(CancelledResponder(cancelled.clone())),
+                info: RequestInfo {
+                    request_id: 1,
+                    interface: Interface::Print.name(),
+                    app_id: None,
+                },
+                _watchdog: None,
+            },
+        );
+
+        let (message_sender, mut message_receiver) = mpsc::channel(1);
+        let (sender, mut receiver) = oneshot::channel();
+        let request = Request::AppChooserUpdateChoices {
+            choices: Vec::new(),
+            sender,
+        };
+        message_sender
+            .try_send(Message::request_with_id(2, request))
+            .unwrap();
+
+        drain_messages(&mut message_receiver, &mut map);
+        assert!(matches!(
+            receiver.try_recv().unwrap(),
+            Err(PortalError::Failed(_))
+        ));
+        assert!(cancelled.get());
+        assert!(map.is_empty());
+        assert!(message_sender.try_send(Message::NameLost).is_err());
     }
 }
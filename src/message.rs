@@ -8,10 +8,20 @@
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use tokio::sync::oneshot::Sender;
+
 use crate::Request;
 
 static REQUEST_ID: AtomicUsize = AtomicUsize::new(1);
 
+/// What is known about an active request, for debugging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestInfo {
+    pub request_id: usize,
+    pub interface: &'static str,
+    pub app_id: Option<String>,
+}
+
 /// A message to the `GLib` world from the ASHPD world.
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
@@ -23,6 +33,13 @@ pub enum Message {
     Done { request_id: usize },
     /// A new request from user.
     Request { request_id: usize, request: Request },
+    /// Debugging aid to list the requests that have a responder.
+    ListRequests { sender: Sender<Vec<RequestInfo>> },
+    /// Another instance took over the name, so cancel all the responders and stop.
+    NameLost,
+    /// Cancel the responders of every request from the app of given ID, like when it is force
+    /// stopped.
+    CancelApp { app_id: String },
 }
 
 impl Message {
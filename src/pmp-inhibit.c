@@ -0,0 +1,440 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Based on the xdg-desktop-portal-gnome
+ */
+
+#include "pmp-config.h"
+
+#include <gio/gio.h>
+
+#include "xdg-desktop-portal-dbus.h"
+
+#include "pmp-inhibit.h"
+#include "pmp-request.h"
+#include "pmp-utils.h"
+
+/*
+ * The Inhibit portal. Inhibitors are handed to gnome-session, which Phosh uses as session
+ * manager. Monitors report whether the screen is locked by following the `ActiveChanged` signal
+ * of the `org.gnome.ScreenSaver` interface Phosh implements. Each monitor is a session exported at
+ * the handle the frontend picked and gets dropped once the session is closed.
+ *
+ * Ending the session is not tracked, so monitors always report it as running.
+ */
+
+#define SESSION_MANAGER_NAME "org.gnome.SessionManager"
+#define SESSION_MANAGER_PATH "/org/gnome/SessionManager"
+#define SESSION_MANAGER_INTERFACE "org.gnome.SessionManager"
+
+#define SCREENSAVER_NAME "org.gnome.ScreenSaver"
+#define SCREENSAVER_PATH "/org/gnome/ScreenSaver"
+#define SCREENSAVER_INTERFACE "org.gnome.ScreenSaver"
+
+/* The `session-state` values of the portal */
+typedef enum {
+  PMP_SESSION_STATE_RUNNING   = 1,
+  PMP_SESSION_STATE_QUERY_END = 2,
+  PMP_SESSION_STATE_ENDING    = 3,
+} PmpSessionState;
+
+static GDBusConnection *session_bus;
+static GDBusProxy *screensaver;
+static gboolean screensaver_active;
+/* The monitor sessions keyed by their handle */
+static GHashTable *monitors;
+
+/* A monitor session */
+
+typedef struct _InhibitMonitor {
+  PmpImplSessionSkeleton parent_instance;
+
+  char                  *id;
+} InhibitMonitor;
+
+typedef struct _InhibitMonitorClass {
+  PmpImplSessionSkeletonClass parent_class;
+} InhibitMonitorClass;
+
+static void inhibit_monitor_skeleton_iface_init (PmpImplSessionIface *iface);
+
+G_DEFINE_TYPE_WITH_CODE (InhibitMonitor, inhibit_monitor, PMP_IMPL_TYPE_SESSION_SKELETON,
+                         G_IMPLEMENT_INTERFACE (PMP_IMPL_TYPE_SESSION,
+                                                inhibit_monitor_skeleton_iface_init))
+
+static gboolean
+handle_monitor_close (PmpImplSession        *object,
+                      GDBusMethodInvocation *invocation)
+{
+  InhibitMonitor *monitor = (InhibitMonitor *)object;
+
+  g_debug ("Closing monitor %s", monitor->id);
+
+  pmp_impl_session_complete_close (object, invocation);
+
+  g_dbus_interface_skeleton_unexport (G_DBUS_INTERFACE_SKELETON (monitor));
+  /* Drops the last reference */
+  g_hash_table_remove (monitors, monitor->id);
+
+  return TRUE;
+}
+
+static void
+inhibit_monitor_skeleton_iface_init (PmpImplSessionIface *iface)
+{
+  iface->handle_close = handle_monitor_close;
+}
+
+static void
+inhibit_monitor_init (InhibitMonitor *monitor)
+{
+}
+
+static void
+inhibit_monitor_finalize (GObject *object)
+{
+  InhibitMonitor *monitor = (InhibitMonitor *)object;
+
+  g_free (monitor->id);
+
+  G_OBJECT_CLASS (inhibit_monitor_parent_class)->finalize (object);
+}
+
+static void
+inhibit_monitor_class_init (InhibitMonitorClass *klass)
+{
+  GObjectClass *object_class = G_OBJECT_CLASS (klass);
+
+  object_class->finalize = inhibit_monitor_finalize;
+}
+
+/**
+ * build_state:
+ * @active: Whether the screen saver, that is the lock screen, is active
+ * @session_state: The state of the login session
+ *
+ * Map the state of the session to the `a{sv}` of the `StateChanged` signal.
+ *
+ * Returns: (transfer floating): The state
+ */
+static GVariant *
+build_state (gboolean active, PmpSessionState session_state)
+{
+  GVariantBuilder state;
+
+  g_variant_builder_init (&state, G_VARIANT_TYPE_VARDICT);
+  g_variant_builder_add (&state, "{sv}", "screensaver-active", g_variant_new_boolean (active));
+  g_variant_builder_add (&state, "{sv}", "session-state", g_variant_new_uint32 (session_state));
+
+  return g_variant_builder_end (&state);
+}
+
+
+static void
+emit_state_changed (PmpImplInhibit *impl, const char *session_handle)
+{
+  GVariant *state = build_state (screensaver_active, PMP_SESSION_STATE_RUNNING);
+
+  pmp_impl_inhibit_emit_state_changed (impl, session_handle, state);
+}
+
+
+static void
+update_screensaver_active (PmpImplInhibit *impl, gboolean active)
+{
+  GHashTableIter iter;
+  const char *session_handle;
+
+  if (screensaver_active == active)
+    return;
+
+  g_debug ("Screen saver %s", active ? "active" : "inactive");
+  screensaver_active = active;
+
+  g_hash_table_iter_init (&iter, monitors);
+  while (g_hash_table_iter_next (&iter, (gpointer *)&session_handle, NULL))
+    emit_state_changed (impl, session_handle);
+}
+
+
+static void
+on_screensaver_signal (GDBusProxy *proxy,
+                       const char *sender_name,
+                       const char *signal_name,
+                       GVariant   *parameters,
+                       gpointer    user_data)
+{
+  PmpImplInhibit *impl = PMP_IMPL_INHIBIT (user_data);
+  gboolean active;
+
+  if (g_strcmp0 (signal_name, "ActiveChanged") != 0)
+    return;
+
+  g_variant_get (parameters, "(b)", &active);
+  update_screensaver_active (impl, active);
+}
+
+
+static void
+on_get_active_finished (GObject      *source_object,
+                        GAsyncResult *result,
+                        gpointer      user_data)
+{
+  PmpImplInhibit *impl = PMP_IMPL_INHIBIT (user_data);
+  g_autoptr (GVariant) ret = NULL;
+  g_autoptr (GError) error = NULL;
+  gboolean active;
+
+  ret = g_dbus_proxy_call_finish (G_DBUS_PROXY (source_object), result, &error);
+  if (ret == NULL) {
+    g_warning ("Failed to get the screen saver state: %s", error->message);
+    return;
+  }
+
+  g_variant_get (ret, "(b)", &active);
+  update_screensaver_active (impl, active);
+}
+
+
+static void
+on_screensaver_proxy_ready (GObject      *source_object,
+                            GAsyncResult *result,
+                            gpointer      user_data)
+{
+  PmpImplInhibit *impl = PMP_IMPL_INHIBIT (user_data);
+  g_autoptr (GError) error = NULL;
+
+  screensaver = g_dbus_proxy_new_finish (result, &error);
+  if (screensaver == NULL) {
+    g_warning ("Failed to watch the screen saver: %s", error->message);
+    return;
+  }
+
+  g_signal_connect (screensaver, "g-signal", G_CALLBACK (on_screensaver_signal), impl);
+  g_dbus_proxy_call (screensaver,
+                     "GetActive",
+                     NULL,
+                     G_DBUS_CALL_FLAGS_NONE,
+                     -1,
+                     NULL,
+                     on_get_active_finished,
+                     impl);
+}
+
+
+static void
+on_uninhibit_finished (GObject      *source_object,
+                       GAsyncResult *result,
+                       gpointer      user_data)
+{
+  g_autoptr (GVariant) ret = NULL;
+  g_autoptr (GError) error = NULL;
+
+  ret = g_dbus_connection_call_finish (G_DBUS_CONNECTION (source_object), result, &error);
+  if (ret == NULL)
+    g_warning ("Failed to uninhibit: %s", error->message);
+}
+
+
+static void
+uninhibit (guint cookie)
+{
+  g_debug ("Uninhibiting %u", cookie);
+  g_dbus_connection_call (session_bus,
+                          SESSION_MANAGER_NAME,
+                          SESSION_MANAGER_PATH,
+                          SESSION_MANAGER_INTERFACE,
+                          "Uninhibit",
+                          g_variant_new ("(u)", cookie),
+                          G_VARIANT_TYPE_UNIT,
+                          G_DBUS_CALL_FLAGS_NONE,
+                          -1,
+                          NULL,
+                          on_uninhibit_finished,
+                          NULL);
+}
+
+
+static gboolean
+handle_inhibit_close (PmpImplRequest        *object,
+                      GDBusMethodInvocation *invocation,
+                      gpointer               user_data)
+{
+  Request *request = (Request *)object;
+
+  uninhibit (GPOINTER_TO_UINT (g_object_get_data (G_OBJECT (request), "cookie")));
+
+  if (request->exported)
+    request_unexport (request);
+
+  pmp_impl_request_complete_close (object, invocation);
+
+  return TRUE;
+}
+
+
+static void
+on_inhibit_finished (GObject      *source_object,
+                     GAsyncResult *result,
+                     gpointer      user_data)
+{
+  g_autoptr (Request) request = user_data;
+  g_autoptr (GVariant) ret = NULL;
+  g_autoptr (GError) error = NULL;
+  guint cookie;
+
+  ret = g_dbus_connection_call_finish (G_DBUS_CONNECTION (source_object), result, &error);
+  if (ret == NULL) {
+    g_warning ("Failed to inhibit: %s", error->message);
+    if (request->exported)
+      request_unexport (request);
+    return;
+  }
+
+  g_variant_get (ret, "(u)", &cookie);
+
+  /* The app is already done with it */
+  if (!request->exported) {
+    uninhibit (cookie);
+    return;
+  }
+
+  g_debug ("Inhibited %s as %u", request->id, cookie);
+  g_object_set_data (G_OBJECT (request), "cookie", GUINT_TO_POINTER (cookie));
+  g_signal_connect (request, "handle-close", G_CALLBACK (handle_inhibit_close), NULL);
+}
+
+
+static gboolean
+handle_inhibit (PmpImplInhibit        *object,
+                GDBusMethodInvocation *invocation,
+                const char            *arg_handle,
+                const char            *arg_app_id,
+                const char            *arg_window,
+                guint                  arg_flags,
+                GVariant              *arg_options)
+{
+  g_autoptr (Request) request = NULL;
+  const char *sender;
+  const char *reason = "";
+
+  g_variant_lookup (arg_options, "reason", "&s", &reason);
+
+  sender = g_dbus_method_invocation_get_sender (invocation);
+  request = request_new (sender, arg_app_id, arg_handle);
+  request_export (request, g_dbus_method_invocation_get_connection (invocation));
+
+  /* The portal flags match the ones of gnome-session */
+  g_dbus_connection_call (session_bus,
+                          SESSION_MANAGER_NAME,
+                          SESSION_MANAGER_PATH,
+                          SESSION_MANAGER_INTERFACE,
+                          "Inhibit",
+                          g_variant_new ("(susu)", arg_app_id, 0, reason, arg_flags),
+                          G_VARIANT_TYPE ("(u)"),
+                          G_DBUS_CALL_FLAGS_NONE,
+                          -1,
+                          NULL,
+                          on_inhibit_finished,
+                          g_object_ref (request));
+
+  pmp_impl_inhibit_complete_inhibit (object, invocation);
+
+  return TRUE;
+}
+
+
+static gboolean
+handle_create_monitor (PmpImplInhibit        *object,
+                       GDBusMethodInvocation *invocation,
+                       const char            *arg_handle,
+                       const char            *arg_session_handle,
+                       const char            *arg_app_id,
+                       const char            *arg_window)
+{
+  g_autoptr (GError) error = NULL;
+  InhibitMonitor *monitor;
+
+  if (g_hash_table_contains (monitors, arg_session_handle)) {
+    g_dbus_method_invocation_return_error (invocation,
+                                           XDG_DESKTOP_PORTAL_ERROR,
+                                           XDG_DESKTOP_PORTAL_ERROR_EXISTS,
+                                           "Monitor %s already exists",
+                                           arg_session_handle);
+    return TRUE;
+  }
+
+  monitor = g_object_new (inhibit_monitor_get_type (), NULL);
+  monitor->id = g_strdup (arg_session_handle);
+
+  if (!g_dbus_interface_skeleton_export (G_DBUS_INTERFACE_SKELETON (monitor),
+                                         g_dbus_method_invocation_get_connection (invocation),
+                                         arg_session_handle,
+                                         &error)) {
+    g_warning ("Failed to export monitor: %s", error->message);
+    g_object_unref (monitor);
+    pmp_impl_inhibit_complete_create_monitor (object, invocation, 2);
+    return TRUE;
+  }
+
+  g_debug ("Created monitor %s for %s", arg_session_handle, arg_app_id);
+  g_hash_table_insert (monitors, monitor->id, monitor);
+
+  pmp_impl_inhibit_complete_create_monitor (object, invocation, 0);
+  /* Let the app know where it starts from */
+  emit_state_changed (object, arg_session_handle);
+
+  return TRUE;
+}
+
+
+static gboolean
+handle_query_end_response (PmpImplInhibit        *object,
+                           GDBusMethodInvocation *invocation,
+                           const char            *arg_session_handle)
+{
+  /* Nothing to do as the session never queries for its end */
+  pmp_impl_inhibit_complete_query_end_response (object, invocation);
+
+  return TRUE;
+}
+
+
+gboolean
+pmp_inhibit_init (GDBusConnection *bus, GError **error)
+{
+  GDBusInterfaceSkeleton *helper;
+
+  session_bus = g_object_ref (bus);
+
+  helper = G_DBUS_INTERFACE_SKELETON (pmp_impl_inhibit_skeleton_new ());
+
+  g_signal_connect (helper, "handle-inhibit", G_CALLBACK (handle_inhibit), NULL);
+  g_signal_connect (helper, "handle-create-monitor", G_CALLBACK (handle_create_monitor), NULL);
+  g_signal_connect (helper, "handle-query-end-response", G_CALLBACK (handle_query_end_response),
+                    NULL);
+
+  monitors = g_hash_table_new_full (g_str_hash, g_str_equal, NULL, g_object_unref);
+
+  if (!g_dbus_interface_skeleton_export (helper,
+                                         bus,
+                                         DESKTOP_PORTAL_OBJECT_PATH,
+                                         error))
+    return FALSE;
+
+  g_dbus_proxy_new (session_bus,
+                    G_DBUS_PROXY_FLAGS_DO_NOT_LOAD_PROPERTIES,
+                    NULL,
+                    SCREENSAVER_NAME,
+                    SCREENSAVER_PATH,
+                    SCREENSAVER_INTERFACE,
+                    NULL,
+                    on_screensaver_proxy_ready,
+                    helper);
+
+  g_debug ("providing %s", g_dbus_interface_skeleton_get_info (helper)->name);
+
+  return TRUE;
+}
@@ -0,0 +1,15 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+#pragma once
+
+#include <gio/gio.h>
+
+G_BEGIN_DECLS
+
+gboolean pmp_inhibit_init (GDBusConnection *bus, GError **error);
+
+G_END_DECLS
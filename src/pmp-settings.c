@@ -301,6 +301,73 @@ settings_handle_read (PmpImplSettings       *object,
   return TRUE;
 }
 
+/* Changes arriving within this window are coalesced, only the last value of each key is emitted */
+#define SETTING_CHANGED_DELAY_MS 100
+
+typedef struct {
+  char     *namespace;
+  char     *key;
+  GVariant *value;
+} PendingChange;
+
+static GHashTable *pending_changes;
+static guint pending_changes_id;
+
+static PendingChange *
+pending_change_new (const char *namespace, const char *key, GVariant *value)
+{
+  PendingChange *change = g_new (PendingChange, 1);
+
+  change->namespace = g_strdup (namespace);
+  change->key = g_strdup (key);
+  change->value = g_variant_ref_sink (value);
+  return change;
+}
+
+static void
+pending_change_free (PendingChange *change)
+{
+  g_free (change->namespace);
+  g_free (change->key);
+  g_variant_unref (change->value);
+  g_free (change);
+}
+
+static gboolean
+emit_pending_changes (gpointer data)
+{
+  PmpImplSettings *impl = PMP_IMPL_SETTINGS (data);
+  GHashTableIter iter;
+  PendingChange *change;
+
+  g_hash_table_iter_init (&iter, pending_changes);
+  while (g_hash_table_iter_next (&iter, NULL, (gpointer *)&change)) {
+    g_debug ("Emitting changed for %s %s", change->namespace, change->key);
+    pmp_impl_settings_emit_setting_changed (impl, change->namespace, change->key, change->value);
+  }
+
+  g_hash_table_remove_all (pending_changes);
+  pending_changes_id = 0;
+
+  return G_SOURCE_REMOVE;
+}
+
+/* Queue a change, restarting the window so a burst of changes results in a single emission */
+static void
+queue_setting_changed (PmpImplSettings *impl,
+                       const char      *namespace,
+                       const char      *key,
+                       GVariant        *value)
+{
+  g_hash_table_replace (pending_changes,
+                        g_strconcat (namespace, " ", key, NULL),
+                        pending_change_new (namespace, key, value));
+
+  g_clear_handle_id (&pending_changes_id, g_source_remove);
+  pending_changes_id = g_timeout_add (SETTING_CHANGED_DELAY_MS, emit_pending_changes, impl);
+  g_source_set_name_by_id (pending_changes_id, "[pmp] emit_pending_changes");
+}
+
 typedef struct {
   PmpImplSettings *self;
   const char      *namespace;
@@ -331,38 +398,38 @@ on_settings_changed (GSettings             *settings,
 {
   g_autoptr (GVariant) new_value = g_settings_get_value (settings, key);
 
-  g_debug ("Emitting changed for %s %s", user_data->namespace, key);
+  g_debug ("Queueing changed for %s %s", user_data->namespace, key);
   if (strcmp (user_data->namespace, "org.gnome.desktop.interface") == 0 &&
       strcmp (key, "enable-animations") == 0)
     sync_animations_enabled (user_data->self);
   else
-    pmp_impl_settings_emit_setting_changed (user_data->self,
-                                            user_data->namespace, key,
-                                            g_variant_new ("v", new_value));
+    queue_setting_changed (user_data->self,
+                           user_data->namespace, key,
+                           g_variant_new ("v", new_value));
 
   if (strcmp (user_data->namespace, "org.gnome.desktop.interface") == 0 &&
       strcmp (key, "accent-color") == 0)
-    pmp_impl_settings_emit_setting_changed (user_data->self,
-                                            "org.freedesktop.appearance", key,
-                                            g_variant_new ("v", get_accent_color ()));
+    queue_setting_changed (user_data->self,
+                           "org.freedesktop.appearance", key,
+                           g_variant_new ("v", get_accent_color ()));
 
   if (strcmp (user_data->namespace, "org.gnome.desktop.interface") == 0 &&
       strcmp (key, "color-scheme") == 0)
-    pmp_impl_settings_emit_setting_changed (user_data->self,
-                                            "org.freedesktop.appearance", key,
-                                            g_variant_new ("v", get_color_scheme ()));
+    queue_setting_changed (user_data->self,
+                           "org.freedesktop.appearance", key,
+                           g_variant_new ("v", get_color_scheme ()));
 
   if (strcmp (user_data->namespace, "org.gnome.desktop.a11y.interface") == 0 &&
       strcmp (key, "high-contrast") == 0) {
-    pmp_impl_settings_emit_setting_changed (user_data->self,
-                                            "org.gnome.desktop.interface", "gtk-theme",
-                                            g_variant_new ("v", get_theme_value ("gtk-theme")));
+    queue_setting_changed (user_data->self,
+                           "org.gnome.desktop.interface", "gtk-theme",
+                           g_variant_new ("v", get_theme_value ("gtk-theme")));
     if (g_variant_is_of_type (new_value, G_VARIANT_TYPE_BOOLEAN)) {
       gboolean hc = g_variant_get_boolean (new_value);
-      pmp_impl_settings_emit_setting_changed (user_data->self,
-                                              "org.freedesktop.appearance",
-                                              "contrast",
-                                              g_variant_new ("v", g_variant_new_uint32 (hc ? 1 : 0)));
+      queue_setting_changed (user_data->self,
+                             "org.freedesktop.appearance",
+                             "contrast",
+                             g_variant_new ("v", g_variant_new_uint32 (hc ? 1 : 0)));
     }
   }
 }
@@ -415,13 +482,13 @@ fontconfig_changed (FcMonitor       *monitor,
   const char *namespace = "org.gnome.fontconfig";
   const char *key = "serial";
 
-  g_debug ("Emitting changed for %s %s", namespace, key);
+  g_debug ("Queueing changed for %s %s", namespace, key);
 
   fontconfig_serial++;
 
-  pmp_impl_settings_emit_setting_changed (impl,
-                                          namespace, key,
-                                          g_variant_new ("v", g_variant_new_int32 (fontconfig_serial)));
+  queue_setting_changed (impl,
+                         namespace, key,
+                         g_variant_new ("v", g_variant_new_int32 (fontconfig_serial)));
 }
 
 static void
@@ -438,10 +505,7 @@ set_enable_animations (PmpImplSettings *impl,
   enable_animations = new_enable_animations;
   enable_animations_variant =
     g_variant_new ("v", g_variant_new_boolean (enable_animations));
-  pmp_impl_settings_emit_setting_changed (impl,
-                                          namespace,
-                                          key,
-                                          enable_animations_variant);
+  queue_setting_changed (impl, namespace, key, enable_animations_variant);
 }
 
 static void
@@ -468,6 +532,8 @@ pmp_settings_init (GDBusConnection *bus, GError **error)
   g_signal_connect (helper, "handle-read-all", G_CALLBACK (settings_handle_read_all), NULL);
 
   settings_hash = g_hash_table_new_full (g_str_hash, g_str_equal, NULL, (GDestroyNotify)settings_bundle_free);
+  pending_changes = g_hash_table_new_full (g_str_hash, g_str_equal, g_free,
+                                           (GDestroyNotify)pending_change_free);
 
   init_settings_table (PMP_IMPL_SETTINGS (helper), settings_hash);
 
@@ -25,6 +25,9 @@
 
 #include "pmp-wallpaper-preview.h"
 
+/* Length of the longer side of the preview, the other side follows the monitor's aspect ratio */
+#define PREVIEW_SIZE 300
+
 enum {
   PROP_0,
   PROP_LOCKSCREEN,
@@ -135,6 +138,37 @@ draw_preview_func (GtkDrawingArea *drawing_area,
   cairo_paint (cr);
 }
 
+/* Match the preview to the monitor's aspect ratio so the wallpaper is framed like on the device */
+static void
+update_size (PmpWallpaperPreview *self)
+{
+  g_autoptr (GdkMonitor) monitor = NULL;
+  GdkRectangle geometry;
+  GListModel *monitors;
+  int width, height;
+
+  monitors = gdk_display_get_monitors (gtk_widget_get_display (GTK_WIDGET (self)));
+  monitor = g_list_model_get_item (monitors, 0);
+  if (monitor == NULL)
+    return;
+
+  gdk_monitor_get_geometry (monitor, &geometry);
+  if (geometry.width <= 0 || geometry.height <= 0)
+    return;
+
+  if (geometry.width >= geometry.height) {
+    width = PREVIEW_SIZE;
+    height = PREVIEW_SIZE * geometry.height / geometry.width;
+    gtk_widget_remove_css_class (GTK_WIDGET (self), "portrait");
+  } else {
+    width = PREVIEW_SIZE * geometry.width / geometry.height;
+    height = PREVIEW_SIZE;
+    gtk_widget_add_css_class (GTK_WIDGET (self), "portrait");
+  }
+
+  gtk_widget_set_size_request (GTK_WIDGET (self), width, height);
+}
+
 static void
 update_clock_label (PmpWallpaperPreview *self,
                     gboolean             force)
@@ -288,6 +322,8 @@ pmp_wallpaper_preview_set_image (PmpWallpaperPreview *self,
                           gnome_bg_changes_with_time (self->bg));
   gtk_stack_set_visible_child (GTK_STACK (self->stack), self->desktop_preview);
 
+  update_size (self);
+
   gtk_drawing_area_set_draw_func (GTK_DRAWING_AREA (self->drawing_area),
                                   draw_preview_func,
                                   self,
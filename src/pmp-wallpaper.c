@@ -37,6 +37,7 @@ typedef struct {
 
   guint                  response;
   char                  *picture_uri;
+  const char * const    *schemas;
   gboolean               on_lockscreen;
   gboolean               on_background;
 } PmpWallpaperDialogHandle;
@@ -73,25 +74,46 @@ send_response (PmpWallpaperDialogHandle *handle)
   wallpaper_dialog_handle_close (handle);
 }
 
-static gboolean
-set_desktop_gsettings (char *uri)
+/**
+ * get_target_schemas:
+ * @set_on: (nullable): The `set-on` option of the request
+ *
+ * Map the `set-on` option to the schemas whose `picture-uri` gets the wallpaper. The desktop
+ * background is in `org.gnome.desktop.background` and the lock screen one in
+ * `org.gnome.desktop.screensaver`. A missing option means both, as per the portal spec.
+ *
+ * Returns: (transfer none) (nullable): The %NULL terminated list of schemas or %NULL if
+ *   @set_on is unknown
+ */
+static const char * const *
+get_target_schemas (const char *set_on)
 {
-  g_autoptr (GSettings) settings = NULL;
-
-  settings = g_settings_new (BACKGROUND_SCHEMA);
+  static const char * const background[] = { BACKGROUND_SCHEMA, NULL };
+  static const char * const lockscreen[] = { SCREENSAVER_SCHEMA, NULL };
+  static const char * const both[] = { BACKGROUND_SCHEMA, SCREENSAVER_SCHEMA, NULL };
+
+  if (set_on == NULL || g_strcmp0 (set_on, "both") == 0)
+    return both;
+  if (g_strcmp0 (set_on, "background") == 0)
+    return background;
+  if (g_strcmp0 (set_on, "lockscreen") == 0)
+    return lockscreen;
 
-  return (g_settings_set_string (settings, "picture-uri", uri) &&
-          g_settings_set_string (settings, "picture-uri-dark", uri) &&
-          g_settings_set_enum (settings, "picture-options", G_DESKTOP_BACKGROUND_STYLE_ZOOM));
+  return NULL;
 }
 
 
 static gboolean
-set_screensaver_gsettings (char *uri)
+set_gsettings (const char *schema, const char *uri)
 {
   g_autoptr (GSettings) settings = NULL;
 
-  settings = g_settings_new (SCREENSAVER_SCHEMA);
+  settings = g_settings_new (schema);
+
+  /* Only the desktop background has a separate picture for the dark style */
+  if (g_strcmp0 (schema, BACKGROUND_SCHEMA) == 0 &&
+      !g_settings_set_string (settings, "picture-uri-dark", uri))
+    return FALSE;
 
   return (g_settings_set_string (settings, "picture-uri", uri) &&
           g_settings_set_enum (settings, "picture-options", G_DESKTOP_BACKGROUND_STYLE_ZOOM));
@@ -110,6 +132,7 @@ on_file_copy_cb (GObject      *source_object,
   g_autofree char *uri = NULL;
   char *contents = NULL;
   gsize length = 0;
+  size_t i;
 
   handle->response = 2;
 
@@ -133,10 +156,12 @@ on_file_copy_cb (GObject      *source_object,
   }
 
   handle->response = 0;
-  if (handle->on_background && set_desktop_gsettings (handle->picture_uri))
-    handle->response = 1;
-  if (handle->on_lockscreen && set_screensaver_gsettings (handle->picture_uri))
-    handle->response = 1;
+  for (i = 0; handle->schemas[i]; i++) {
+    if (!set_gsettings (handle->schemas[i], handle->picture_uri)) {
+      g_warning ("Failed to set wallpaper in '%s'", handle->schemas[i]);
+      handle->response = 2;
+    }
+  }
 out:
   send_response (handle);
 }
@@ -200,7 +225,7 @@ handle_set_wallpaper_uri (PmpImplWallpaper      *object,
 {
   g_autoptr (Request) request = NULL;
   PmpWallpaperDialogHandle *handle;
-  const char *sender, *set_on;
+  const char *sender, *set_on = NULL;
   gboolean show_preview = FALSE;
   PmpExternalWin *external_parent = NULL;
   GdkSurface *surface;
@@ -218,12 +243,15 @@ handle_set_wallpaper_uri (PmpImplWallpaper      *object,
   handle->invocation = invocation;
   handle->request = g_object_ref (request);
 
-  if (g_strcmp0 (set_on, "lockscreen") == 0)
-    handle->on_lockscreen = TRUE;
-  else if (g_strcmp0 (set_on, "background") == 0)
-    handle->on_background = TRUE;
-  else if (g_strcmp0 (set_on, "both") == 0)
-    handle->on_lockscreen = handle->on_background = TRUE;
+  handle->schemas = get_target_schemas (set_on);
+  if (handle->schemas == NULL) {
+    g_warning ("Unknown set-on value '%s'", set_on);
+    handle->response = 2;
+    send_response (handle);
+    return TRUE;
+  }
+  handle->on_background = g_strv_contains (handle->schemas, BACKGROUND_SCHEMA);
+  handle->on_lockscreen = g_strv_contains (handle->schemas, SCREENSAVER_SCHEMA);
 
   if (!show_preview) {
     set_wallpaper (handle, arg_uri);
@@ -6,16 +6,25 @@
  * Author: Arun Mani J <arun.mani@tether.to>
  */
 
+use ashpd::backend::access::AccessResponse;
 use ashpd::backend::account::UserInformationOptions;
 use ashpd::backend::app_chooser::{Choice, ChooserOptions, DesktopID};
 use ashpd::backend::file_chooser::{
     OpenFileOptions, SaveFileOptions, SaveFilesOptions, SelectedFiles,
 };
+use ashpd::backend::usb::AccessOptions;
 use ashpd::backend::Result;
 use ashpd::desktop::account::UserInformation;
-use ashpd::{AppID, WindowIdentifierType};
+use ashpd::desktop::file_chooser::Choice as AccessChoice;
+use ashpd::desktop::print::{PageSetup, PreparePrint, Settings};
+use ashpd::desktop::usb::UsbDevice;
+use ashpd::zvariant::OwnedFd;
+use ashpd::{AppID, PortalError, WindowIdentifierType};
+use gtk::glib;
 use tokio::sync::oneshot::Sender;
 
+const LOG_DOMAIN: &str = "xdpp-request";
+
 /// Essential information about the external application which does a portal request.
 #[derive(Debug)]
 pub struct Application {
@@ -23,10 +32,79 @@ pub struct Application {
     pub window_identifier: Option<WindowIdentifierType>,
 }
 
+/// The portal interfaces served by the backend. Each has its own kind of responder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interface {
+    Access,
+    Account,
+    AppChooser,
+    Email,
+    FileChooser,
+    Print,
+    Usb,
+}
+
+impl Interface {
+    /// Name of the portal interface on D-Bus.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Access => "org.freedesktop.impl.portal.Access",
+            Self::Account => "org.freedesktop.impl.portal.Account",
+            Self::AppChooser => "org.freedesktop.impl.portal.AppChooser",
+            Self::Email => "org.freedesktop.impl.portal.Email",
+            Self::FileChooser => "org.freedesktop.impl.portal.FileChooser",
+            Self::Print => "org.freedesktop.impl.portal.Print",
+            Self::Usb => "org.freedesktop.impl.portal.Usb",
+        }
+    }
+
+    /// Name of the interface the portal frontend offers to apps for this interface. Access has no
+    /// interface of its own, the camera is the one asking through it most.
+    #[must_use]
+    pub fn frontend_name(self) -> &'static str {
+        match self {
+            Self::Access => "org.freedesktop.portal.Camera",
+            Self::Account => "org.freedesktop.portal.Account",
+            Self::AppChooser => "org.freedesktop.portal.OpenURI",
+            Self::Email => "org.freedesktop.portal.Email",
+            Self::FileChooser => "org.freedesktop.portal.FileChooser",
+            Self::Print => "org.freedesktop.portal.Print",
+            Self::Usb => "org.freedesktop.portal.Usb",
+        }
+    }
+}
+
+/// How the `GLib` world finds the responder for a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// The request starts a new dialog, so it gets a new responder.
+    New,
+    /// The request updates a dialog, so it goes to the responder that already handles the request
+    /// of the same ID.
+    Existing,
+}
+
 /// Different types of portal requests. The `GLib` world picks the matching responder and passes the
 /// request to it. Each request has a `sender` through which the responder will send the reply.
 #[derive(Debug)]
 pub enum Request {
+    AccessAccessDialog {
+        application: Application,
+        title: String,
+        subtitle: String,
+        body: String,
+        modal: Option<bool>,
+        deny_label: Option<String>,
+        grant_label: Option<String>,
+        icon_name: Option<String>,
+        choices: Vec<AccessChoice>,
+        /// Whether the app asks for the camera, which gets a dialog naming the app and the camera.
+        camera: bool,
+        /// Where to remember the decision if the user asks to, `None` if it can not be remembered.
+        decision_key: Option<String>,
+        sender: Sender<Result<AccessResponse>>,
+    },
     AccountGetUserInformation {
         application: Application,
         options: UserInformationOptions,
@@ -42,6 +120,13 @@ pub enum Request {
         choices: Vec<DesktopID>,
         sender: Sender<Result<()>>,
     },
+    EmailComposeEmail {
+        application: Application,
+        /// The `mailto:` URI of the email, without the invalid addresses.
+        uri: String,
+        activation_token: Option<String>,
+        sender: Sender<Result<()>>,
+    },
     FileChooserOpenFile {
         application: Application,
         title: String,
@@ -60,4 +145,102 @@ pub enum Request {
         options: SaveFilesOptions,
         sender: Sender<Result<SelectedFiles>>,
     },
+    PrintPreparePrint {
+        application: Application,
+        title: String,
+        settings: Box<Settings>,
+        page_setup: Box<PageSetup>,
+        modal: Option<bool>,
+        accept_label: Option<String>,
+        sender: Sender<Result<PreparePrint>>,
+    },
+    PrintPrint {
+        application: Application,
+        title: String,
+        fd: OwnedFd,
+        token: Option<u32>,
+        sender: Sender<Result<()>>,
+    },
+    UsbAcquireDevices {
+        application: Application,
+        devices: Vec<(String, UsbDevice, AccessOptions)>,
+        sender: Sender<Result<Vec<(String, AccessOptions)>>>,
+    },
+}
+
+impl Request {
+    /// The portal interface the request belongs to.
+    #[must_use]
+    pub fn interface(&self) -> Interface {
+        match self {
+            Self::AccessAccessDialog { .. } => Interface::Access,
+            Self::AccountGetUserInformation { .. } => Interface::Account,
+            Self::AppChooserChooseApplication { .. } | Self::AppChooserUpdateChoices { .. } => {
+                Interface::AppChooser
+            }
+            Self::EmailComposeEmail { .. } => Interface::Email,
+            Self::FileChooserOpenFile { .. }
+            | Self::FileChooserSaveFile { .. }
+            | Self::FileChooserSaveFiles { .. } => Interface::FileChooser,
+            Self::PrintPreparePrint { .. } | Self::PrintPrint { .. } => Interface::Print,
+            Self::UsbAcquireDevices { .. } => Interface::Usb,
+        }
+    }
+
+    /// Whether the request needs a new responder or goes to an existing one.
+    #[must_use]
+    pub fn route(&self) -> Route {
+        match self {
+            Self::AppChooserUpdateChoices { .. } => Route::Existing,
+            Self::AccessAccessDialog { .. }
+            | Self::AccountGetUserInformation { .. }
+            | Self::AppChooserChooseApplication { .. }
+            | Self::EmailComposeEmail { .. }
+            | Self::FileChooserOpenFile { .. }
+            | Self::FileChooserSaveFile { .. }
+            | Self::FileChooserSaveFiles { .. }
+            | Self::PrintPreparePrint { .. }
+            | Self::PrintPrint { .. }
+            | Self::UsbAcquireDevices { .. } => Route::New,
+        }
+    }
+
+    /// The application doing the request, if the request carries it.
+    #[must_use]
+    pub fn application(&self) -> Option<&Application> {
+        match self {
+            Self::AccessAccessDialog { application, .. }
+            | Self::AccountGetUserInformation { application, .. }
+            | Self::AppChooserChooseApplication { application, .. }
+            | Self::EmailComposeEmail { application, .. }
+            | Self::FileChooserOpenFile { application, .. }
+            | Self::FileChooserSaveFile { application, .. }
+            | Self::FileChooserSaveFiles { application, .. }
+            | Self::PrintPreparePrint { application, .. }
+            | Self::PrintPrint { application, .. }
+            | Self::UsbAcquireDevices { application, .. } => Some(application),
+            Self::AppChooserUpdateChoices { .. } => None,
+        }
+    }
+
+    /// Reply to the request with an error, for when no responder can handle it.
+    pub fn reply_error(self, error: PortalError) {
+        let sent = match self {
+            Self::AccessAccessDialog { sender, .. } => sender.send(Err(error)).is_ok(),
+            Self::AccountGetUserInformation { sender, .. } => sender.send(Err(error)).is_ok(),
+            Self::AppChooserChooseApplication { sender, .. } => sender.send(Err(error)).is_ok(),
+            Self::AppChooserUpdateChoices { sender, .. }
+            | Self::EmailComposeEmail { sender, .. }
+            | Self::PrintPrint { sender, .. } => sender.send(Err(error)).is_ok(),
+            Self::FileChooserOpenFile { sender, .. }
+            | Self::FileChooserSaveFile { sender, .. }
+            | Self::FileChooserSaveFiles { sender, .. } => sender.send(Err(error)).is_ok(),
+            Self::PrintPreparePrint { sender, .. } => sender.send(Err(error)).is_ok(),
+            Self::UsbAcquireDevices { sender, .. } => sender.send(Err(error)).is_ok(),
+        };
+
+        if !sent {
+            glib::g_critical!(LOG_DOMAIN, "Unable to send response through sender");
+        }
+    }
 }
@@ -7,19 +7,58 @@
  */
 use std::collections::HashMap;
 use std::sync::RwLock;
+use std::time::{Duration, Instant};
 
 use ashpd::async_trait::async_trait;
 use ashpd::backend::Result;
 use ashpd::desktop::HandleToken;
 use ashpd::PortalError;
 use gtk::glib;
+use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot::error::RecvError;
 use tokio::sync::oneshot::Receiver;
 
-use crate::{Message, Request};
+use crate::{Message, Request, XdppError};
 
 const LOG_DOMAIN: &str = "xdpp-requester";
 
+const DISPATCH_FAILED: &str = "Unable to pass the request to the backend";
+const REPLY_FAILED: &str = "Request was dropped without a reply";
+const UNKNOWN_REQUEST: &str = "No pending request for the handle";
+
+/// How long a handle may stay registered before it is taken for leaked, like when the frontend
+/// closed it under a different token. Dialogs are not expected to stay open for that long.
+const STALE_REQUEST_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn dispatch_error(error: &SendError<Message>) -> PortalError {
+    glib::g_critical!(LOG_DOMAIN, "Error: {error}");
+    XdppError::ChannelClosed(String::from(DISPATCH_FAILED)).into()
+}
+
+fn reply_error(error: &RecvError) -> PortalError {
+    glib::g_critical!(LOG_DOMAIN, "Error: {error}");
+    XdppError::ChannelClosed(String::from(REPLY_FAILED)).into()
+}
+
+/// A request a requester routes `close` and updates to.
+pub struct PendingRequest {
+    request_id: usize,
+    since: Instant,
+}
+
+impl PendingRequest {
+    fn new(request_id: usize) -> Self {
+        Self {
+            request_id,
+            since: Instant::now(),
+        }
+    }
+}
+
+/// The pending requests of a requester, keyed by the handle token of the frontend.
+pub type RequestMap = RwLock<HashMap<HandleToken, PendingRequest>>;
+
 /// A requester is responsible for getting the portal requests from the ASHPD world and passing it
 /// to the `GLib` world. It gets a `sender` through which it can communicate with the `GLib` world
 /// about the requests.
@@ -27,17 +66,46 @@ const LOG_DOMAIN: &str = "xdpp-requester";
 pub trait Requester {
     fn new(sender: Sender<Message>) -> Self;
     fn sender(&self) -> &Sender<Message>;
-    fn map(&self) -> &RwLock<HashMap<HandleToken, usize>>;
+    fn map(&self) -> &RequestMap;
 
-    async fn send_cancel(&self, token: &HandleToken) {
-        let request_id;
-        {
-            let mut map = self.map().write().unwrap();
-            request_id = map.remove(token);
+    /// Take the request of `token` out of the map. An unknown token hints at a mismatched
+    /// handle, so stale requests get swept then.
+    fn take_request(&self, token: &HandleToken) -> Option<usize> {
+        let request = self.map().write().unwrap().remove(token);
+        if request.is_none() {
+            glib::g_critical!(LOG_DOMAIN, "Unknown handle: {token}");
+            glib::g_debug!(
+                LOG_DOMAIN,
+                "Known handles: {:?}",
+                self.map().read().unwrap().keys().collect::<Vec<_>>()
+            );
+            self.sweep_stale(Instant::now());
         }
+        request.map(|request| request.request_id)
+    }
 
+    /// Forget the requests registered for longer than `STALE_REQUEST_AGE` at `now`, so that a
+    /// handle that is never closed does not stay in the map forever. Returns the forgotten ids.
+    fn sweep_stale(&self, now: Instant) -> Vec<usize> {
+        let mut stale = Vec::new();
+        self.map().write().unwrap().retain(|token, request| {
+            if now.saturating_duration_since(request.since) < STALE_REQUEST_AGE {
+                return true;
+            }
+            glib::g_warning!(
+                LOG_DOMAIN,
+                "Forgetting stale request {} of handle {token}",
+                request.request_id
+            );
+            stale.push(request.request_id);
+            false
+        });
+        stale
+    }
+
+    async fn send_cancel(&self, token: &HandleToken) {
+        let request_id = self.take_request(token);
         if request_id.is_none() {
-            glib::g_critical!(LOG_DOMAIN, "Unknown handle: {token:#?}");
             return;
         }
 
@@ -48,14 +116,8 @@ pub trait Requester {
     }
 
     async fn send_done(&self, token: &HandleToken) {
-        let request_id;
-        {
-            let mut map = self.map().write().unwrap();
-            request_id = map.remove(token);
-        }
-
+        let request_id = self.take_request(token);
         if request_id.is_none() {
-            glib::g_critical!(LOG_DOMAIN, "Unknown handle: {token}");
             return;
         }
 
@@ -75,14 +137,16 @@ pub trait Requester {
 
         let (request_id, message) = Message::request(request);
 
-        if let Err(error) = self.sender().send(message).await {
-            glib::g_critical!(LOG_DOMAIN, "Error: {error}");
-            return Err(PortalError::Failed(String::from("Unknown error")));
-        }
-
+        // Register the handle before dispatching, so that a `close` or an update arriving right
+        // after the request can always be routed to it.
         {
             let mut map = self.map().write().unwrap();
-            map.insert(token.clone(), request_id);
+            map.insert(token.clone(), PendingRequest::new(request_id));
+        }
+
+        if let Err(error) = self.sender().send(message).await {
+            self.map().write().unwrap().remove(token);
+            return Err(dispatch_error(&error));
         }
 
         let result = match receiver.await {
@@ -90,10 +154,7 @@ pub trait Requester {
                 glib::g_debug!(LOG_DOMAIN, "Response: {response:#?}");
                 response
             }
-            Err(error) => {
-                glib::g_critical!(LOG_DOMAIN, "Error: {error}");
-                Err(PortalError::Failed(String::from("Unknown error")))
-            }
+            Err(error) => Err(reply_error(&error)),
         };
 
         result
@@ -110,20 +171,19 @@ pub trait Requester {
         let message;
         {
             let map = self.map().read().unwrap();
-            message = if let Some(request_id) = map.get(token) {
+            message = if let Some(pending) = map.get(token) {
                 Message::Request {
-                    request_id: *request_id,
+                    request_id: pending.request_id,
                     request,
                 }
             } else {
                 glib::g_critical!(LOG_DOMAIN, "Unknown request");
-                return Err(PortalError::Failed(String::from("Unknown error")));
+                return Err(XdppError::ResponderMissing(String::from(UNKNOWN_REQUEST)).into());
             }
         }
 
         if let Err(error) = self.sender().send(message).await {
-            glib::g_critical!(LOG_DOMAIN, "Error: {error}");
-            return Err(PortalError::Failed(String::from("Unknown error")));
+            return Err(dispatch_error(&error));
         }
 
         let result = match receiver.await {
@@ -131,12 +191,111 @@ pub trait Requester {
                 glib::g_debug!(LOG_DOMAIN, "Response: {response:#?}");
                 response
             }
-            Err(error) => {
-                glib::g_critical!(LOG_DOMAIN, "Error: {error}");
-                Err(PortalError::Failed(String::from("Unknown error")))
-            }
+            Err(error) => Err(reply_error(&error)),
         };
 
         return result;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use tokio::runtime::Runtime;
+    use tokio::sync::{mpsc, oneshot};
+
+    use super::*;
+
+    struct TestRequester {
+        sender: Sender<Message>,
+        map: RequestMap,
+    }
+
+    impl Requester for TestRequester {
+        fn new(sender: Sender<Message>) -> Self {
+            TestRequester {
+                sender,
+                map: RwLock::new(HashMap::new()),
+            }
+        }
+
+        fn sender(&self) -> &Sender<Message> {
+            &self.sender
+        }
+
+        fn map(&self) -> &RequestMap {
+            &self.map
+        }
+    }
+
+    fn send_update_choices(requester: &TestRequester, runtime: &Runtime) -> Result<()> {
+        let token = HandleToken::try_from("xdpp_test").unwrap();
+        let (sender, receiver) = oneshot::channel();
+        let request = Request::AppChooserUpdateChoices {
+            choices: Vec::new(),
+            sender,
+        };
+        runtime.block_on(requester.send_request(&token, request, receiver))
+    }
+
+    #[test]
+    fn test_send_request_dispatch_failed() {
+        let runtime = Runtime::new().unwrap();
+        let (sender, receiver) = mpsc::channel(1);
+        drop(receiver);
+
+        let requester = TestRequester::new(sender);
+        let result = send_update_choices(&requester, &runtime);
+        assert!(matches!(result, Err(PortalError::Failed(message)) if message == DISPATCH_FAILED));
+    }
+
+    #[test]
+    fn test_send_request_reply_failed() {
+        let runtime = Runtime::new().unwrap();
+        let (sender, mut receiver) = mpsc::channel(1);
+        // Drop the request, and with it the reply sender, as soon as it arrives.
+        runtime.spawn(async move {
+            drop(receiver.recv().await);
+        });
+
+        let requester = TestRequester::new(sender);
+        let result = send_update_choices(&requester, &runtime);
+        assert!(matches!(result, Err(PortalError::Failed(message)) if message == REPLY_FAILED));
+    }
+
+    #[test]
+    fn test_update_request_unknown() {
+        let runtime = Runtime::new().unwrap();
+        let (sender, _receiver) = mpsc::channel(1);
+        let requester = TestRequester::new(sender);
+
+        let token = HandleToken::try_from("xdpp_test").unwrap();
+        let (sender, receiver) = oneshot::channel();
+        let request = Request::AppChooserUpdateChoices {
+            choices: Vec::new(),
+            sender,
+        };
+        let result = runtime.block_on(requester.update_request(&token, request, receiver));
+        assert!(
+            matches!(result, Err(PortalError::NotFound(message)) if message == UNKNOWN_REQUEST)
+        );
+    }
+
+    #[test]
+    fn test_sweep_stale() {
+        let (sender, _receiver) = mpsc::channel(1);
+        let requester = TestRequester::new(sender);
+        let token = HandleToken::try_from("xdpp_test").unwrap();
+        requester
+            .map()
+            .write()
+            .unwrap()
+            .insert(token.clone(), PendingRequest::new(1));
+
+        assert!(requester.sweep_stale(Instant::now()).is_empty());
+        assert!(requester.map().read().unwrap().contains_key(&token));
+
+        let later = Instant::now() + STALE_REQUEST_AGE;
+        assert_eq!(requester.sweep_stale(later), [1]);
+        assert!(requester.map().read().unwrap().is_empty());
+    }
+}
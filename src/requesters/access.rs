@@ -0,0 +1,270 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use ashpd::async_trait::async_trait;
+use ashpd::backend::access::{AccessImpl, AccessOptions, AccessResponse};
+use ashpd::backend::request::RequestImpl;
+use ashpd::backend::Result;
+use ashpd::desktop::{HandleToken, Icon};
+use ashpd::{AppID, PortalError, WindowIdentifierType};
+use gtk::glib;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+
+use crate::decisions::{can_remember, decision_key, forget, remembered};
+use crate::{Application, Interface, Message, Request, RequestMap, Requester};
+
+/*
+ * Handler for Access interface requests. A decision the user asked to remember is replied right
+ * away, without asking the `GLib` world for a dialog.
+ *
+ * The frontend asks for the camera through this interface too. Its requests are told apart by their
+ * icon, and their decision is tied to the "Camera" resource instead of the texts, which carry the
+ * translated app name. The frontend keeps the camera permission too and only asks when it has none,
+ * like after the user reset it. A remembered camera decision is stale then, so it is forgotten and
+ * the dialog is shown again.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-access";
+
+/// The icon the camera portal of the frontend asks with.
+const CAMERA_ICON: &str = "camera-web-symbolic";
+
+pub struct Access {
+    sender: Sender<Message>,
+    map: RequestMap,
+}
+
+impl Requester for Access {
+    fn new(sender: Sender<Message>) -> Self {
+        Access {
+            sender,
+            map: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn sender(&self) -> &Sender<Message> {
+        &self.sender
+    }
+
+    fn map(&self) -> &RequestMap {
+        &self.map
+    }
+}
+
+/// The remembered decision of `key` to reply with, looked up through `remembered`. A camera
+/// decision is dropped through `forget` instead, see above.
+fn remembered_decision(
+    key: &str,
+    camera: bool,
+    remembered: impl FnOnce(&str) -> Option<bool>,
+    forget: impl FnOnce(&str),
+) -> Option<bool> {
+    let granted = remembered(key)?;
+    if camera {
+        glib::g_debug!(LOG_DOMAIN, "Camera permission was reset, forgetting {key}");
+        forget(key);
+        return None;
+    }
+    Some(granted)
+}
+
+/// The reply of a remembered decision. Only decisions without choices are remembered.
+fn remembered_reply(granted: bool) -> Result<AccessResponse> {
+    if granted {
+        Ok(AccessResponse::default())
+    } else {
+        Err(PortalError::Cancelled(String::from(
+            "Denied by remembered choice",
+        )))
+    }
+}
+
+#[async_trait]
+impl RequestImpl for Access {
+    async fn close(&self, token: HandleToken) {
+        self.send_cancel(&token).await;
+    }
+}
+
+#[async_trait]
+impl AccessImpl for Access {
+    async fn access_dialog(
+        &self,
+        token: HandleToken,
+        app_id: Option<AppID>,
+        window_identifier: Option<WindowIdentifierType>,
+        title: String,
+        subtitle: String,
+        body: String,
+        options: AccessOptions,
+    ) -> Result<AccessResponse> {
+        let icon_name = match options.icon() {
+            Some(Icon::Names(names)) => names.into_iter().next(),
+            _ => None,
+        };
+        let camera = icon_name.as_deref() == Some(CAMERA_ICON);
+        let decision_key = options
+            .choices()
+            .is_empty()
+            .then(|| {
+                if camera {
+                    decision_key(app_id.as_ref(), Interface::Access, &["Camera"])
+                } else {
+                    decision_key(
+                        app_id.as_ref(),
+                        Interface::Access,
+                        &[&title, &subtitle, &body],
+                    )
+                }
+            })
+            .flatten()
+            .filter(|_| can_remember());
+        let granted = decision_key
+            .as_deref()
+            .and_then(|key| remembered_decision(key, camera, remembered, forget));
+        if let Some(granted) = granted {
+            glib::g_debug!(LOG_DOMAIN, "Replying to {token} with a remembered decision");
+            return remembered_reply(granted);
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let request = Request::AccessAccessDialog {
+            application: Application {
+                app_id,
+                window_identifier,
+            },
+            title,
+            subtitle,
+            body,
+            modal: options.is_modal(),
+            deny_label: options.deny_label().map(String::from),
+            grant_label: options.grant_label().map(String::from),
+            icon_name,
+            choices: options.choices().to_vec(),
+            camera,
+            decision_key,
+            sender,
+        };
+        let result = self.send_request(&token, request, receiver).await;
+        self.send_done(&token).await;
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::requesters::test_utils;
+    use crate::responders::test_utils::options;
+
+    #[test]
+    fn test_access_dialog() {
+        let token = HandleToken::try_from("xdpp_test").unwrap();
+        let app_id = AppID::from_str("mobi.phosh.Test").unwrap();
+
+        let result = test_utils::intercept(
+            |access: Access| async move {
+                access
+                    .access_dialog(
+                        token,
+                        Some(app_id),
+                        None,
+                        String::from("Allow Camera?"),
+                        String::new(),
+                        String::from("Test wants to use the camera."),
+                        options(&[]),
+                    )
+                    .await
+            },
+            |request| {
+                let Request::AccessAccessDialog {
+                    title,
+                    camera,
+                    decision_key,
+                    sender,
+                    ..
+                } = request
+                else {
+                    panic!("Unexpected request");
+                };
+                assert_eq!(title, "Allow Camera?");
+                assert!(!camera);
+                // Without the schema installed, there is nowhere to remember the decision.
+                assert_eq!(decision_key.is_some(), can_remember());
+                sender.send(Ok(AccessResponse::default())).unwrap();
+            },
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_remembered_decision() {
+        let forgotten = std::cell::Cell::new(false);
+        let forget = |_: &str| forgotten.set(true);
+
+        assert_eq!(
+            remembered_decision("key", false, |_| Some(true), forget),
+            Some(true)
+        );
+        assert!(!forgotten.get());
+        assert_eq!(remembered_decision("key", false, |_| None, forget), None);
+
+        // The frontend only asks about the camera once its permission was reset, so the decision
+        // is forgotten and the dialog shown.
+        assert_eq!(
+            remembered_decision("key", true, |_| Some(true), forget),
+            None
+        );
+        assert!(forgotten.get());
+    }
+
+    #[test]
+    fn test_camera_access() {
+        let token = HandleToken::try_from("xdpp_test").unwrap();
+        let app_id = AppID::from_str("mobi.phosh.Test").unwrap();
+        let camera_key = decision_key(Some(&app_id), Interface::Access, &["Camera"]);
+
+        let result = test_utils::intercept(
+            |access: Access| async move {
+                access
+                    .access_dialog(
+                        token,
+                        Some(app_id),
+                        None,
+                        String::from("Allow Test to Use the Camera?"),
+                        String::from("Test wants to access camera devices."),
+                        String::new(),
+                        options(&[("icon", CAMERA_ICON.into())]),
+                    )
+                    .await
+            },
+            move |request| {
+                let Request::AccessAccessDialog {
+                    camera,
+                    decision_key,
+                    sender,
+                    ..
+                } = request
+                else {
+                    panic!("Unexpected request");
+                };
+                assert!(camera);
+                // The decision does not depend on the translated texts.
+                assert_eq!(decision_key, camera_key.filter(|_| can_remember()));
+                sender.send(Ok(AccessResponse::default())).unwrap();
+            },
+        );
+        assert!(result.is_ok());
+    }
+}
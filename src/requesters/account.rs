@@ -19,7 +19,7 @@ use ashpd::{AppID, WindowIdentifierType};
 use tokio::sync::mpsc::Sender;
 use tokio::sync::oneshot;
 
-use crate::{Application, Message, Request, Requester};
+use crate::{Application, Message, Request, RequestMap, Requester};
 
 /*
  * Handler for Account interface requests.
@@ -27,7 +27,7 @@ use crate::{Application, Message, Request, Requester};
 
 pub struct Account {
     sender: Sender<Message>,
-    map: RwLock<HashMap<HandleToken, usize>>,
+    map: RequestMap,
 }
 
 impl Requester for Account {
@@ -42,7 +42,7 @@ impl Requester for Account {
         &self.sender
     }
 
-    fn map(&self) -> &RwLock<HashMap<HandleToken, usize>> {
+    fn map(&self) -> &RequestMap {
         &self.map
     }
 }
@@ -77,3 +77,47 @@ impl AccountImpl for Account {
         return result;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use ashpd::url::Url;
+    use ashpd::zvariant::Value;
+
+    use super::*;
+    use crate::requesters::test_utils;
+    use crate::responders::test_utils::options;
+
+    #[test]
+    fn test_get_user_information() {
+        let token = HandleToken::try_from("xdpp_test").unwrap();
+        let app_id = AppID::from_str("mobi.phosh.Test").unwrap();
+        let options = options(&[("reason", Value::from("To greet you"))]);
+
+        let result = test_utils::intercept(
+            |account: Account| async move {
+                account
+                    .get_user_information(token, Some(app_id), None, options)
+                    .await
+            },
+            |request| {
+                let Request::AccountGetUserInformation {
+                    application,
+                    options,
+                    sender,
+                } = request
+                else {
+                    panic!("Unexpected request");
+                };
+                assert_eq!(application.app_id.unwrap().to_string(), "mobi.phosh.Test");
+                assert_eq!(options.reason(), Some("To greet you"));
+
+                let image = Url::parse("file:///home/phosh/.face").unwrap();
+                let info = UserInformation::new("phosh", "Phosh", image);
+                sender.send(Ok(info)).unwrap();
+            },
+        );
+        assert_eq!(result.unwrap().id(), "phosh");
+    }
+}
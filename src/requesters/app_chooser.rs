@@ -18,7 +18,7 @@ use ashpd::{AppID, WindowIdentifierType};
 use tokio::sync::mpsc::Sender;
 use tokio::sync::oneshot;
 
-use crate::{Application, Message, Request, Requester};
+use crate::{Application, Message, Request, RequestMap, Requester};
 
 /*
  * Handler for AppChooser interface requests.
@@ -26,7 +26,7 @@ use crate::{Application, Message, Request, Requester};
 
 pub struct AppChooser {
     sender: Sender<Message>,
-    map: RwLock<HashMap<HandleToken, usize>>,
+    map: RequestMap,
 }
 
 impl Requester for AppChooser {
@@ -41,7 +41,7 @@ impl Requester for AppChooser {
         &self.sender
     }
 
-    fn map(&self) -> &RwLock<HashMap<HandleToken, usize>> {
+    fn map(&self) -> &RequestMap {
         &self.map
     }
 }
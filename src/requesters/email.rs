@@ -0,0 +1,256 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use ashpd::async_trait::async_trait;
+use ashpd::backend::email::{EmailImpl, Options};
+use ashpd::backend::request::RequestImpl;
+use ashpd::backend::Result;
+use ashpd::desktop::HandleToken;
+use ashpd::{AppID, WindowIdentifierType};
+use gtk::glib;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+
+use crate::utils::is_valid_email;
+use crate::{Application, Message, Request, RequestMap, Requester, XdppError};
+
+/*
+ * Handler for Email interface requests. The addresses are checked before the `mailto:` URI is
+ * built, since a malformed one breaks the URI. Invalid addresses are dropped and the request fails
+ * if none of the ones given are left. A `mailto:` URI can not carry attachments, so requests with
+ * attachments are rejected instead of composing an email without them.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-email";
+
+pub struct Email {
+    sender: Sender<Message>,
+    map: RequestMap,
+}
+
+impl Requester for Email {
+    fn new(sender: Sender<Message>) -> Self {
+        Email {
+            sender,
+            map: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn sender(&self) -> &Sender<Message> {
+        &self.sender
+    }
+
+    fn map(&self) -> &RequestMap {
+        &self.map
+    }
+}
+
+/// The valid ones of `addresses`.
+fn valid_addresses<'a>(addresses: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    addresses
+        .into_iter()
+        .filter(|address| {
+            let valid = is_valid_email(address);
+            if !valid {
+                glib::g_warning!(LOG_DOMAIN, "Dropping invalid address {address:?}");
+            }
+            valid
+        })
+        .collect()
+}
+
+/// The `mailto:` URI of an email, as in RFC 6068.
+fn mailto_uri(
+    to: &[&str],
+    cc: &[&str],
+    bcc: &[&str],
+    subject: Option<&str>,
+    body: Option<&str>,
+) -> String {
+    let escape = |text: &str, allowed| glib::Uri::escape_string(text, allowed, false);
+    let addresses = |addresses: &[&str]| {
+        addresses
+            .iter()
+            .map(|address| escape(address, Some("@")).to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    let mut fields = Vec::new();
+    if !cc.is_empty() {
+        fields.push(format!("cc={}", addresses(cc)));
+    }
+    if !bcc.is_empty() {
+        fields.push(format!("bcc={}", addresses(bcc)));
+    }
+    if let Some(subject) = subject {
+        fields.push(format!("subject={}", escape(subject, None)));
+    }
+    if let Some(body) = body {
+        fields.push(format!("body={}", escape(body, None)));
+    }
+
+    let mut uri = format!("mailto:{}", addresses(to));
+    if !fields.is_empty() {
+        uri.push('?');
+        uri.push_str(&fields.join("&"));
+    }
+    uri
+}
+
+#[async_trait]
+impl RequestImpl for Email {
+    async fn close(&self, token: HandleToken) {
+        self.send_cancel(&token).await;
+    }
+}
+
+#[async_trait]
+impl EmailImpl for Email {
+    async fn compose(
+        &self,
+        token: HandleToken,
+        app_id: Option<AppID>,
+        window_identifier: Option<WindowIdentifierType>,
+        options: Options,
+    ) -> Result<()> {
+        if !options.attachments().is_empty() {
+            glib::g_warning!(
+                LOG_DOMAIN,
+                "Rejecting {token}, attachments are not supported"
+            );
+            let error = XdppError::InvalidRequest(String::from("Attachments are not supported"));
+            return Err(error.into());
+        }
+
+        let given = usize::from(options.address().is_some())
+            + options.addresses().len()
+            + options.cc().len()
+            + options.bcc().len();
+        let to = valid_addresses(
+            options
+                .address()
+                .into_iter()
+                .chain(options.addresses().iter().map(String::as_str)),
+        );
+        let cc = valid_addresses(options.cc().iter().map(String::as_str));
+        let bcc = valid_addresses(options.bcc().iter().map(String::as_str));
+        if given > 0 && to.is_empty() && cc.is_empty() && bcc.is_empty() {
+            return Err(XdppError::InvalidRequest(String::from("No valid address")).into());
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let request = Request::EmailComposeEmail {
+            application: Application {
+                app_id,
+                window_identifier,
+            },
+            uri: mailto_uri(&to, &cc, &bcc, options.subject(), options.body()),
+            activation_token: options.activation_token().map(ToString::to_string),
+            sender,
+        };
+        let result = self.send_request(&token, request, receiver).await;
+        self.send_done(&token).await;
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ashpd::zvariant::Value;
+    use ashpd::PortalError;
+
+    use super::*;
+    use crate::requesters::test_utils;
+    use crate::responders::test_utils::options;
+
+    #[test]
+    fn test_mailto_uri() {
+        assert_eq!(mailto_uri(&[], &[], &[], None, None), "mailto:");
+        assert_eq!(
+            mailto_uri(
+                &["a@phosh.mobi", "b+list@phosh.mobi"],
+                &["c@phosh.mobi"],
+                &[],
+                Some("Hi & bye"),
+                Some("See you?\n"),
+            ),
+            "mailto:a@phosh.mobi,b%2Blist@phosh.mobi?cc=c@phosh.mobi\
+             &subject=Hi%20%26%20bye&body=See%20you%3F%0A"
+        );
+    }
+
+    #[test]
+    fn test_compose_drops_invalid() {
+        let token = HandleToken::try_from("xdpp_test").unwrap();
+
+        let result = test_utils::intercept(
+            |email: Email| async move {
+                let addresses = Value::from(vec!["user@phosh.mobi", "garbage"]);
+                email
+                    .compose(
+                        token,
+                        None,
+                        None,
+                        options(&[("addresses", addresses), ("bcc", vec!["@"].into())]),
+                    )
+                    .await
+            },
+            |request| {
+                let Request::EmailComposeEmail { uri, sender, .. } = request else {
+                    panic!("Unexpected request");
+                };
+                assert_eq!(uri, "mailto:user@phosh.mobi");
+                sender.send(Ok(())).unwrap();
+            },
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_compose_no_valid_address() {
+        let token = HandleToken::try_from("xdpp_test").unwrap();
+
+        let result = test_utils::intercept(
+            |email: Email| async move {
+                email
+                    .compose(token, None, None, options(&[("address", "garbage".into())]))
+                    .await
+            },
+            |_| panic!("No request was expected"),
+        );
+        assert!(matches!(result, Err(PortalError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_compose_attachments() {
+        let token = HandleToken::try_from("xdpp_test").unwrap();
+
+        let result = test_utils::intercept(
+            |email: Email| async move {
+                let attachments = Value::from(vec!["file:///home/user/notes.txt"]);
+                email
+                    .compose(
+                        token,
+                        None,
+                        None,
+                        options(&[
+                            ("address", "user@phosh.mobi".into()),
+                            ("attachments", attachments),
+                        ]),
+                    )
+                    .await
+            },
+            |_| panic!("No request was expected"),
+        );
+        assert!(matches!(result, Err(PortalError::InvalidArgument(_))));
+    }
+}
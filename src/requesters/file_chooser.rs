@@ -20,7 +20,7 @@ use ashpd::{AppID, WindowIdentifierType};
 use tokio::sync::mpsc::Sender;
 use tokio::sync::oneshot;
 
-use crate::{Application, Message, Request, Requester};
+use crate::{Application, Message, Request, RequestMap, Requester};
 
 /*
  * Handler for FileChooser interface requests.
@@ -28,7 +28,7 @@ use crate::{Application, Message, Request, Requester};
 
 pub struct FileChooser {
     sender: Sender<Message>,
-    map: RwLock<HashMap<HandleToken, usize>>,
+    map: RequestMap,
 }
 
 impl Requester for FileChooser {
@@ -43,7 +43,7 @@ impl Requester for FileChooser {
         &self.sender
     }
 
-    fn map(&self) -> &RwLock<HashMap<HandleToken, usize>> {
+    fn map(&self) -> &RequestMap {
         &self.map
     }
 }
@@ -6,10 +6,20 @@
  * Author: Arun Mani J <arun.mani@tether.to>
  */
 
+mod access;
 mod account;
 mod app_chooser;
+mod email;
 mod file_chooser;
+mod print;
+#[cfg(test)]
+pub(crate) mod test_utils;
+mod usb;
 
+pub use access::Access;
 pub use account::Account;
 pub use app_chooser::AppChooser;
+pub use email::Email;
 pub use file_chooser::FileChooser;
+pub use print::Print;
+pub use usb::Usb;
@@ -0,0 +1,112 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use ashpd::async_trait::async_trait;
+use ashpd::backend::print::{PreparePrintOptions, PrintImpl, PrintOptions};
+use ashpd::backend::request::RequestImpl;
+use ashpd::backend::Result;
+use ashpd::desktop::print::{PageSetup, PreparePrint, Settings};
+use ashpd::desktop::HandleToken;
+use ashpd::zvariant::OwnedFd;
+use ashpd::{AppID, WindowIdentifierType};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+
+use crate::{Application, Message, Request, RequestMap, Requester};
+
+/*
+ * Handler for Print interface requests.
+ */
+
+pub struct Print {
+    sender: Sender<Message>,
+    map: RequestMap,
+}
+
+impl Requester for Print {
+    fn new(sender: Sender<Message>) -> Self {
+        Print {
+            sender,
+            map: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn sender(&self) -> &Sender<Message> {
+        &self.sender
+    }
+
+    fn map(&self) -> &RequestMap {
+        &self.map
+    }
+}
+
+#[async_trait]
+impl RequestImpl for Print {
+    async fn close(&self, token: HandleToken) {
+        self.send_cancel(&token).await;
+    }
+}
+
+#[async_trait]
+impl PrintImpl for Print {
+    async fn prepare_print(
+        &self,
+        token: HandleToken,
+        app_id: Option<AppID>,
+        window_identifier: Option<WindowIdentifierType>,
+        title: String,
+        settings: Settings,
+        page_setup: PageSetup,
+        options: PreparePrintOptions,
+    ) -> Result<PreparePrint> {
+        let (sender, receiver) = oneshot::channel();
+        let request = Request::PrintPreparePrint {
+            application: Application {
+                app_id,
+                window_identifier,
+            },
+            title,
+            settings: Box::new(settings),
+            page_setup: Box::new(page_setup),
+            modal: options.is_modal(),
+            accept_label: options.accept_label().map(String::from),
+            sender,
+        };
+        let result = self.send_request(&token, request, receiver).await;
+        self.send_done(&token).await;
+        return result;
+    }
+
+    async fn print(
+        &self,
+        token: HandleToken,
+        app_id: Option<AppID>,
+        window_identifier: Option<WindowIdentifierType>,
+        title: String,
+        fd: OwnedFd,
+        options: PrintOptions,
+    ) -> Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        let request = Request::PrintPrint {
+            application: Application {
+                app_id,
+                window_identifier,
+            },
+            title,
+            fd,
+            token: options.token(),
+            sender,
+        };
+        let result = self.send_request(&token, request, receiver).await;
+        self.send_done(&token).await;
+        return result;
+    }
+}
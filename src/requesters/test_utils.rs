@@ -0,0 +1,47 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::future::Future;
+
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+use crate::{Message, Request, Requester};
+
+/*
+ * Helpers to exercise requesters from unit tests.
+ *
+ * `intercept` builds a requester on a test channel, so that the request it sends can be inspected
+ * in place of the GLib world. No display or D-Bus connection is needed.
+ */
+
+const BUFFER: usize = 4;
+
+/// Run `call` with a new requester and hand the request it sends to `respond`, which should check
+/// it and reply. Returns what `call` returned.
+pub fn intercept<R, F, Fut, T, G>(call: F, respond: G) -> T
+where
+    R: Requester,
+    F: FnOnce(R) -> Fut,
+    Fut: Future<Output = T>,
+    G: FnOnce(Request) + Send + 'static,
+{
+    let runtime = Runtime::new().unwrap();
+    let (sender, mut receiver) = mpsc::channel(BUFFER);
+    runtime.spawn(async move {
+        let mut respond = Some(respond);
+        while let Some(message) = receiver.recv().await {
+            if let Message::Request { request, .. } = message {
+                let respond = respond.take().expect("Only one request was expected");
+                respond(request);
+            }
+        }
+    });
+
+    runtime.block_on(call(R::new(sender)))
+}
@@ -0,0 +1,80 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use ashpd::async_trait::async_trait;
+use ashpd::backend::request::RequestImpl;
+use ashpd::backend::usb::{AccessOptions, AcquireDevicesOptions, UsbImpl};
+use ashpd::backend::Result;
+use ashpd::desktop::usb::UsbDevice;
+use ashpd::desktop::HandleToken;
+use ashpd::{AppID, WindowIdentifierType};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+
+use crate::{Application, Message, Request, RequestMap, Requester};
+
+/*
+ * Handler for Usb interface requests.
+ */
+
+pub struct Usb {
+    sender: Sender<Message>,
+    map: RequestMap,
+}
+
+impl Requester for Usb {
+    fn new(sender: Sender<Message>) -> Self {
+        Usb {
+            sender,
+            map: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn sender(&self) -> &Sender<Message> {
+        &self.sender
+    }
+
+    fn map(&self) -> &RequestMap {
+        &self.map
+    }
+}
+
+#[async_trait]
+impl RequestImpl for Usb {
+    async fn close(&self, token: HandleToken) {
+        self.send_cancel(&token).await;
+    }
+}
+
+#[async_trait]
+impl UsbImpl for Usb {
+    async fn acquire_devices(
+        &self,
+        token: HandleToken,
+        window_identifier: Option<WindowIdentifierType>,
+        app_id: Option<AppID>,
+        devices: Vec<(String, UsbDevice, AccessOptions)>,
+        _options: AcquireDevicesOptions,
+    ) -> Result<Vec<(String, AccessOptions)>> {
+        let (sender, receiver) = oneshot::channel();
+        let request = Request::UsbAcquireDevices {
+            application: Application {
+                app_id,
+                window_identifier,
+            },
+            devices,
+            sender,
+        };
+        let result = self.send_request(&token, request, receiver).await;
+        self.send_done(&token).await;
+        return result;
+    }
+}
@@ -6,12 +6,97 @@
  * Author: Arun Mani J <arun.mani@tether.to>
  */
 
+use std::cell::Cell;
+
+use ashpd::backend::Result;
+use gtk::glib;
+use tokio::sync::oneshot::Sender;
+
 use crate::Request;
 
+const LOG_DOMAIN: &str = "xdpp-responder";
+
 /// A responder reacts to the portal request, gathers input from the user and returns the reply to
 /// it. While processing, if the request gets cancelled, then [`Responder.cancel`](Responder.cancel)
 /// will be called.
+///
+/// Responders are created and called on the `GLib` main loop, which also dispatches every other
+/// request. They must not block it. Slow work that does not touch widgets, like decoding or
+/// encoding images, goes to [`gio::spawn_blocking`](gtk::gio::spawn_blocking) and its result is
+/// awaited from a future spawned with [`glib::spawn_future_local`](gtk::glib::spawn_future_local).
 pub trait Responder {
     fn respond(&self, request: Request);
     fn cancel(&self);
 }
+
+/// The reply to the request a responder is handling. It is sent at most once, so that paths racing
+/// to reply, like a cancel and the user's choice, can not reply twice. Later replies are dropped.
+pub struct Reply<T> {
+    sender: Cell<Option<Sender<Result<T>>>>,
+}
+
+impl<T> Reply<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            sender: Cell::new(None),
+        }
+    }
+
+    /// Wait for the reply of a new request.
+    pub fn set(&self, sender: Sender<Result<T>>) {
+        self.sender.set(Some(sender));
+    }
+
+    /// Whether the request is still waiting for its reply.
+    #[must_use]
+    pub fn is_pending(&self) -> bool {
+        let sender = self.sender.take();
+        let pending = sender.is_some();
+        self.sender.set(sender);
+        pending
+    }
+
+    /// Send `response` unless the request was already replied to. Returns whether it was sent.
+    pub fn send(&self, response: Result<T>) -> bool {
+        let Some(sender) = self.sender.take() else {
+            glib::g_debug!(LOG_DOMAIN, "Request was already replied to, dropping reply");
+            return false;
+        };
+        if sender.send(response).is_err() {
+            glib::g_critical!(LOG_DOMAIN, "Unable to send response through sender");
+            return false;
+        }
+        true
+    }
+}
+
+impl<T> Default for Reply<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ashpd::PortalError;
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    #[test]
+    fn test_reply_once() {
+        let reply = Reply::new();
+        assert!(!reply.is_pending());
+
+        let (sender, mut receiver) = oneshot::channel();
+        reply.set(sender);
+        assert!(reply.is_pending());
+        assert!(reply.send(Ok(1)));
+        let error = PortalError::Cancelled(String::from("Cancelled by user"));
+        assert!(!reply.send(Err(error)));
+        assert!(!reply.is_pending());
+
+        assert_eq!(receiver.try_recv().unwrap().unwrap(), 1);
+    }
+}
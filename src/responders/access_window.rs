@@ -0,0 +1,301 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::cell::RefCell;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use ashpd::backend::access::AccessResponse;
+use ashpd::backend::Result;
+use ashpd::desktop::file_chooser::Choice;
+use ashpd::PortalError;
+use gtk::glib::subclass::InitializingObject;
+use gtk::{glib, CompositeTemplate, TemplateChild};
+
+use crate::decisions::remember;
+use crate::utils::{
+    accept_label_or, escape_markup, get_application_name, gettextf, initial_choice, parent_window,
+};
+use crate::{Reply, Request, Responder};
+
+/*
+ * `AccessWindow` handles the Access interface. The frontend uses it to ask the user whether an app
+ * may access something, like the camera. The title, subtitle and body come from the frontend and
+ * the choices are shown below them as rows. Camera requests get a title naming the camera and the
+ * app instead.
+ *
+ * When the decision can be remembered, a switch lets the user skip the dialog the next time the
+ * app asks for the same. The Access requester looks the decision up before a dialog is shown.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-access-window";
+
+/// A row for a choice of the request, along with the id of the choice.
+pub enum ChoiceRow {
+    Switch(String, adw::SwitchRow),
+    /// The options of the choice, in the order of the rows in the combo.
+    Combo(String, Vec<String>, adw::ComboRow),
+}
+
+impl ChoiceRow {
+    fn new(choice: &Choice) -> Self {
+        let id = choice.id().to_string();
+        let title = escape_markup(choice.label());
+        let initial = initial_choice(choice);
+        let pairs = choice.pairs();
+        if pairs.is_empty() {
+            let row = adw::SwitchRow::builder()
+                .title(title)
+                .active(initial == "true")
+                .build();
+            return Self::Switch(id, row);
+        }
+
+        let labels: Vec<&str> = pairs.iter().map(|(_, label)| *label).collect();
+        let position = pairs
+            .iter()
+            .position(|(key, _)| *key == initial)
+            .unwrap_or(0);
+        let row = adw::ComboRow::builder()
+            .title(title)
+            .model(&gtk::StringList::new(&labels))
+            .selected(u32::try_from(position).unwrap_or(0))
+            .build();
+        let keys = pairs.iter().map(|(key, _)| (*key).to_string()).collect();
+        Self::Combo(id, keys, row)
+    }
+
+    fn row(&self) -> &adw::PreferencesRow {
+        match self {
+            Self::Switch(_, row) => row.upcast_ref(),
+            Self::Combo(_, _, row) => row.upcast_ref(),
+        }
+    }
+
+    /// The id of the choice and the option the user ended up with.
+    fn selection(&self) -> (&str, &str) {
+        match self {
+            Self::Switch(id, row) => (id, if row.is_active() { "true" } else { "false" }),
+            Self::Combo(id, keys, row) => {
+                let position = usize::try_from(row.selected()).unwrap_or(0);
+                (id, keys.get(position).map_or("", String::as_str))
+            }
+        }
+    }
+}
+
+mod imp {
+    #[allow(clippy::wildcard_imports)]
+    use super::*;
+
+    #[derive(CompositeTemplate, Default)]
+    #[template(resource = "/mobi/phosh/xdpp/ui/access_window.ui")]
+    pub struct AccessWindow {
+        #[template_child]
+        pub window_title: TemplateChild<adw::WindowTitle>,
+        #[template_child]
+        pub deny_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub grant_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub icon: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub body_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub choices_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub remember_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub remember_row: TemplateChild<adw::SwitchRow>,
+
+        pub rows: RefCell<Vec<ChoiceRow>>,
+        pub decision_key: RefCell<Option<String>>,
+
+        pub reply: Reply<AccessResponse>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for AccessWindow {
+        const NAME: &'static str = "XdppAccessWindow";
+        type Type = super::AccessWindow;
+        type ParentType = adw::Window;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for AccessWindow {}
+
+    impl WidgetImpl for AccessWindow {}
+
+    impl WindowImpl for AccessWindow {}
+
+    impl AdwWindowImpl for AccessWindow {}
+
+    #[gtk::template_callbacks]
+    impl AccessWindow {
+        #[template_callback]
+        fn on_deny_clicked(&self, _button: &gtk::Button) {
+            self.remember_decision(false);
+            let error = PortalError::Cancelled(String::from("Denied by user"));
+            self.send_response(Err(error));
+        }
+
+        #[template_callback]
+        fn on_grant_clicked(&self, _button: &gtk::Button) {
+            self.remember_decision(true);
+            let response = self
+                .rows
+                .borrow()
+                .iter()
+                .map(ChoiceRow::selection)
+                .fold(AccessResponse::default(), |response, (id, value)| {
+                    response.choice(id, value)
+                });
+            self.send_response(Ok(response));
+        }
+
+        fn remember_decision(&self, granted: bool) {
+            if !self.remember_row.is_active() {
+                return;
+            }
+            if let Some(key) = self.decision_key.borrow().as_deref() {
+                remember(key, granted);
+            }
+        }
+
+        pub fn send_response(&self, response: Result<AccessResponse>) {
+            self.reply.send(response);
+            self.obj().close();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct AccessWindow(ObjectSubclass<imp::AccessWindow>)
+        @extends adw::Window, gtk::Window, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Native, gtk::Root, gtk::ShortcutManager;
+}
+
+impl AccessWindow {
+    #[must_use]
+    pub fn new() -> Self {
+        glib::Object::builder().build()
+    }
+}
+
+impl Default for AccessWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Responder for AccessWindow {
+    fn respond(&self, request: Request) {
+        if let Request::AccessAccessDialog {
+            application,
+            title,
+            subtitle,
+            body,
+            modal,
+            deny_label,
+            grant_label,
+            icon_name,
+            choices,
+            camera,
+            decision_key,
+            sender,
+        } = request
+        {
+            let imp = self.imp();
+
+            // Show the app and the resource asked for, like the other permission dialogs.
+            let (title, subtitle) = if camera {
+                let subtitle = match get_application_name(&application) {
+                    Some(app_name) => gettextf("{} wants to use the camera", &[&app_name]),
+                    None => gettextf("An app wants to use the camera", &[]),
+                };
+                (gettextf("Camera", &[]), subtitle)
+            } else {
+                (title, subtitle)
+            };
+
+            imp.window_title.set_title(&title);
+            imp.window_title.set_subtitle(&subtitle);
+            self.set_title(Some(&title));
+            imp.body_label.set_label(&body);
+            imp.body_label.set_visible(!body.is_empty());
+            self.update_property(&[gtk::accessible::Property::Description(&body)]);
+
+            if let Some(icon_name) = icon_name {
+                imp.icon.set_icon_name(Some(&icon_name));
+                imp.icon.set_visible(true);
+            }
+
+            imp.deny_btn.set_label(&accept_label_or(
+                deny_label.as_deref(),
+                &gettextf("Deny", &[]),
+            ));
+            imp.grant_btn.set_label(&accept_label_or(
+                grant_label.as_deref(),
+                &gettextf("Allow", &[]),
+            ));
+
+            let mut rows = imp.rows.borrow_mut();
+            for choice in &choices {
+                let row = ChoiceRow::new(choice);
+                imp.choices_group.add(row.row());
+                rows.push(row);
+            }
+            imp.choices_group.set_visible(!rows.is_empty());
+
+            imp.remember_group.set_visible(decision_key.is_some());
+            *imp.decision_key.borrow_mut() = decision_key;
+
+            imp.reply.set(sender);
+
+            parent_window(self, &application, modal);
+
+            self.present();
+        } else {
+            glib::g_critical!(LOG_DOMAIN, "Unknown request {request:#?}");
+            panic!();
+        }
+    }
+
+    fn cancel(&self) {
+        let error = PortalError::Cancelled(String::from("Cancelled by application"));
+        self.imp().send_response(Err(error));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_choice_row() {
+        crate::responders::test_utils::run(|| {
+            let choice = Choice::new("resolution", "Resolution", "hd")
+                .insert("sd", "Standard")
+                .insert("hd", "High");
+            let row = ChoiceRow::new(&choice);
+            assert_eq!(row.selection(), ("resolution", "hd"));
+
+            let row = ChoiceRow::new(&Choice::boolean("flash", "Flash", true));
+            assert_eq!(row.selection(), ("flash", "true"));
+        });
+    }
+}
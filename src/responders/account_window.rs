@@ -7,6 +7,7 @@
  */
 
 use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
 
 use adw::prelude::*;
 use adw::subclass::prelude::*;
@@ -16,21 +17,278 @@ use ashpd::url::Url;
 use ashpd::PortalError;
 use gtk::glib::subclass::InitializingObject;
 use gtk::{gdk, gio, glib, CompositeTemplate, TemplateChild};
-use tokio::sync::oneshot::Sender;
 
-use crate::utils::{get_application_name, gettextf};
-use crate::{Request, Responder};
+use crate::lib_config::AVATAR_SOURCES;
+use crate::utils::{confirm, escape_markup, get_application_name, gettextf, parent_window, Busy};
+use crate::{Application, Reply, Request, Responder, XdppError};
 
 /*
  * `AccountWindow` handles the Account interface. It shows a dialog which displays the information
  * of user from the system environment. The user can change it as per their requirement and agree to
- * share it with the requesting application. The default profile picture of user is loaded as
- * `$HOME/.face`.
+ * share it with the requesting application. The default profile picture of user comes from
+ * AccountsService, like on the login screen, or `$HOME/.face`, as configured. AccountsService may
+ * point to a remote picture, like one from a directory service, which is fetched once and cached. A
+ * picture the user picked instead is remembered once shared and offered the next time, along with
+ * a way to reset it to the system one. When the requesting app can not be identified, a banner
+ * warns the user and sharing needs another confirmation.
  */
 
 const LOG_DOMAIN: &str = "xdpp-account-window";
 
 const FACE_FILE: &str = ".face";
+const ACCOUNTS_NAME: &str = "org.freedesktop.Accounts";
+const ACCOUNTS_PATH: &str = "/org/freedesktop/Accounts";
+const ACCOUNTS_USER_INTERFACE: &str = "org.freedesktop.Accounts.User";
+const ACCOUNTS_TIMEOUT_MS: i32 = 1000;
+const STATE_DIR: &str = "xdg-desktop-portal-phrosh";
+const PICTURE_FILE: &str = "account-picture.png";
+const MAX_REASON_LENGTH: usize = 200;
+/// Width and height of the shared profile picture in pixels. It does not follow the scale of the
+/// output the dialog is on, so that the picture is equally sharp wherever the app shows it.
+const PICTURE_SIZE: i32 = 480;
+
+/// The scale to render an avatar of the given size at to get a picture of at least
+/// [`PICTURE_SIZE`], which is exact when the size divides it.
+fn picture_scale(avatar_size: i32) -> i32 {
+    let avatar_size = avatar_size.max(1);
+    (PICTURE_SIZE + avatar_size - 1) / avatar_size
+}
+
+/// Whether the file looks like an image from its name, to reject other drops on the avatar.
+fn is_image(file: &gio::File) -> bool {
+    let Some(name) = file.basename() else {
+        return false;
+    };
+    let (content_type, _) = gio::content_type_guess(Some(name), None);
+    gio::content_type_is_mime_type(&content_type, "image/*")
+}
+
+fn is_unknown_app(application: &Application) -> bool {
+    get_application_name(application).is_none()
+}
+
+/// The username to share. The portal allows an empty one, which is sent when the user chose to
+/// keep the username to themselves.
+fn shared_username(share: bool, username: &str) -> &str {
+    if share {
+        username
+    } else {
+        ""
+    }
+}
+
+/// Prepare the reason given by the app for display. Line breaks become spaces and other control
+/// characters are dropped, so that the reason stays on a few lines. It is cut at
+/// [`MAX_REASON_LENGTH`] characters, with an ellipsis to show that there is more.
+fn format_reason(reason: &str) -> String {
+    let reason: String = reason
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+    let reason = reason.trim();
+
+    if reason.chars().count() > MAX_REASON_LENGTH {
+        let mut truncated: String = reason.chars().take(MAX_REASON_LENGTH).collect();
+        truncated.truncate(truncated.trim_end().len());
+        truncated.push('…');
+        truncated
+    } else {
+        reason.to_string()
+    }
+}
+
+/// Save the picture as a PNG file in `directory`, so that it can be shared by URI.
+fn save_picture(texture: &gdk::Texture, directory: &Path) -> Result<Url> {
+    let path = directory.join(format!(
+        "{}-profile-picture.png",
+        glib::uuid_string_random()
+    ));
+    texture.save_to_png(&path).map_err(|error| {
+        glib::g_critical!(LOG_DOMAIN, "Unable to save picture: {error}");
+        PortalError::from(XdppError::Internal(String::from("Unable to save picture")))
+    })?;
+    Url::from_file_path(&path).map_err(|()| {
+        glib::g_critical!(LOG_DOMAIN, "Invalid picture path {}", path.display());
+        PortalError::from(XdppError::Internal(String::from("Unable to save picture")))
+    })
+}
+
+/// The icon `AccountsService` keeps for the user, as shown on the login screen. It is usually a
+/// path, but can also be the URI of a remote picture.
+async fn accounts_service_icon() -> Option<String> {
+    let connection = gio::bus_get_future(gio::BusType::System).await.ok()?;
+    let user_name = glib::user_name();
+    let reply = connection
+        .call_future(
+            Some(ACCOUNTS_NAME),
+            ACCOUNTS_PATH,
+            ACCOUNTS_NAME,
+            "FindUserByName",
+            Some(&(user_name.to_str()?,).to_variant()),
+            Some(glib::VariantTy::new("(o)").unwrap()),
+            gio::DBusCallFlags::NONE,
+            ACCOUNTS_TIMEOUT_MS,
+        )
+        .await
+        .ok()?;
+    let (user_path,) = reply.get::<(glib::variant::ObjectPath,)>()?;
+
+    let reply = connection
+        .call_future(
+            Some(ACCOUNTS_NAME),
+            user_path.as_str(),
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            Some(&(ACCOUNTS_USER_INTERFACE, "IconFile").to_variant()),
+            Some(glib::VariantTy::new("(v)").unwrap()),
+            gio::DBusCallFlags::NONE,
+            ACCOUNTS_TIMEOUT_MS,
+        )
+        .await
+        .ok()?;
+    let (icon_file,) = reply.get::<(glib::Variant,)>()?;
+    let icon_file = icon_file.get::<String>()?;
+    (!icon_file.is_empty()).then_some(icon_file)
+}
+
+/// Whether the picture has to be fetched rather than read from a local disk.
+fn is_remote(file: &gio::File) -> bool {
+    !file.has_uri_scheme("file")
+}
+
+/// Turn a picture location, a path or a URI, into a file. Local files have to exist, while remote
+/// ones are checked only when fetched.
+fn picture_file(location: &str) -> Option<gio::File> {
+    let file = if location.contains("://") {
+        gio::File::for_uri(location)
+    } else {
+        gio::File::for_path(location)
+    };
+    (is_remote(&file) || file.path().is_some_and(|path| path.is_file())).then_some(file)
+}
+
+/// Find the system picture of the user, trying the sources in [`AVATAR_SOURCES`] order.
+async fn system_picture() -> Option<gio::File> {
+    for source in AVATAR_SOURCES {
+        let location = match *source {
+            "accounts-service" => accounts_service_icon().await,
+            "face" => Some(
+                glib::home_dir()
+                    .join(FACE_FILE)
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            source => {
+                glib::g_warning!(LOG_DOMAIN, "Unknown avatar source `{source}`");
+                None
+            }
+        };
+        if let Some(file) = location.as_deref().and_then(picture_file) {
+            return Some(file);
+        }
+    }
+    None
+}
+
+/// Read and decode the picture off the main loop. Cancelling stops reading the file right away,
+/// while a decode in progress is finished and its result dropped.
+async fn load_texture(file: &gio::File, cancellable: &gio::Cancellable) -> Option<gdk::Texture> {
+    let contents = gio::CancellableFuture::new(file.load_bytes_future(), cancellable.clone())
+        .await
+        .ok()?;
+    let (bytes, _) = contents
+        .map_err(|error| glib::g_debug!(LOG_DOMAIN, "Unable to read {}: {error}", file.uri()))
+        .ok()?;
+    let texture = gio::spawn_blocking(move || gdk::Texture::from_bytes(&bytes))
+        .await
+        .ok()?
+        .map_err(|error| glib::g_debug!(LOG_DOMAIN, "Unable to decode picture: {error}"))
+        .ok()?;
+    (!cancellable.is_cancelled()).then_some(texture)
+}
+
+/// Where the remote picture at `uri` is cached.
+fn cached_picture_path(uri: &str) -> PathBuf {
+    let checksum = glib::compute_checksum_for_string(glib::ChecksumType::Sha256, uri)
+        .map(|checksum| checksum.to_string())
+        .unwrap_or_default();
+    let mut path = glib::user_cache_dir();
+    path.push(STATE_DIR);
+    path.push(format!("{checksum}.png"));
+    path
+}
+
+/// Load the picture at `file`. A remote picture is fetched once and read from `cache` afterwards.
+async fn load_cached_texture(
+    file: &gio::File,
+    cache: &Path,
+    cancellable: &gio::Cancellable,
+) -> Option<gdk::Texture> {
+    if !is_remote(file) {
+        return load_texture(file, cancellable).await;
+    }
+
+    if cache.is_file() {
+        let cached = load_texture(&gio::File::for_path(cache), cancellable).await;
+        if cached.is_some() {
+            return cached;
+        }
+    }
+
+    glib::g_debug!(LOG_DOMAIN, "Fetching picture {}", file.uri());
+    let texture = load_texture(file, cancellable).await?;
+    let cache = cache.to_path_buf();
+    let cached = texture.clone();
+    gio::spawn_blocking(move || {
+        if let Some(parent) = cache.parent() {
+            if let Err(error) = std::fs::create_dir_all(parent) {
+                glib::g_warning!(LOG_DOMAIN, "Unable to create cache directory: {error}");
+                return;
+            }
+        }
+        if let Err(error) = cached.save_to_png(&cache) {
+            glib::g_warning!(LOG_DOMAIN, "Unable to cache picture: {error}");
+        }
+    })
+    .await
+    .ok();
+    Some(texture)
+}
+
+fn remembered_picture_path() -> PathBuf {
+    let mut path = glib::user_data_dir();
+    path.push(STATE_DIR);
+    path.push(PICTURE_FILE);
+    path
+}
+
+/// Remember the picture at `path` for the next request, or forget it when there is none.
+fn remember_picture(texture: Option<&gdk::Texture>, path: &Path) {
+    let Some(texture) = texture else {
+        match std::fs::remove_file(path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                glib::g_warning!(LOG_DOMAIN, "Unable to forget picture: {error}");
+            }
+            _ => {}
+        }
+        return;
+    };
+
+    if let Some(parent) = path.parent() {
+        if let Err(error) = std::fs::create_dir_all(parent) {
+            glib::g_warning!(LOG_DOMAIN, "Unable to create state directory: {error}");
+            return;
+        }
+    }
+
+    if let Err(error) = texture.save_to_png(path) {
+        glib::g_warning!(LOG_DOMAIN, "Unable to remember picture: {error}");
+    }
+}
 
 mod imp {
     #[allow(clippy::wildcard_imports)]
@@ -39,11 +297,19 @@ mod imp {
     #[derive(CompositeTemplate, Default)]
     #[template(resource = "/mobi/phosh/xdpp/ui/account_window.ui")]
     pub struct AccountWindow {
+        #[template_child]
+        pub window_title: TemplateChild<adw::WindowTitle>,
         #[template_child]
         pub avatar: TemplateChild<adw::Avatar>,
         #[template_child]
+        pub avatar_spinner: TemplateChild<gtk::Spinner>,
+        #[template_child]
+        pub share_btn: TemplateChild<gtk::Button>,
+        #[template_child]
         pub del_btn: TemplateChild<gtk::Button>,
         #[template_child]
+        pub reset_btn: TemplateChild<gtk::Button>,
+        #[template_child]
         pub file_dialog: TemplateChild<gtk::FileDialog>,
         #[template_child]
         pub name_row: TemplateChild<adw::EntryRow>,
@@ -52,11 +318,21 @@ mod imp {
         #[template_child]
         pub reason_row: TemplateChild<adw::ActionRow>,
         #[template_child]
+        pub share_username_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
         pub username_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub unknown_app_banner: TemplateChild<adw::Banner>,
 
         pub cancellable: RefCell<gio::Cancellable>,
 
-        pub sender: Cell<Option<Sender<Result<UserInformation>>>>,
+        /// Loading the picture keeps the dialog busy, so that a stale one is not shared.
+        pub busy: Busy,
+
+        /// Whether the avatar shows the system picture, which needs not be remembered.
+        pub system_picture: Cell<bool>,
+
+        pub reply: Reply<UserInformation>,
     }
 
     #[glib::object_subclass]
@@ -102,28 +378,198 @@ mod imp {
         }
 
         #[template_callback]
-        fn on_share_clicked(&self, _button: &gtk::Button) {
-            let texture = self.avatar.draw_to_texture(self.avatar.scale_factor());
-            let (file, _) = gio::File::new_tmp(Some("XXXXXX-profile-picture.png")).unwrap();
-            texture.save_to_png(file.path().unwrap()).unwrap();
-            let info = UserInformation::new(
-                &self.username_row.text(),
-                &self.name_row.text(),
-                Url::parse(&file.uri()).unwrap(),
+        fn on_share_clicked(&self, button: &gtk::Button) {
+            if !self.unknown_app_banner.is_revealed() {
+                self.share(button);
+                return;
+            }
+
+            let confirmed = confirm(
+                &*self.obj(),
+                &gettextf("Share With Unknown App?", &[]),
+                &gettextf(
+                    "The app asking for your details could not be identified. Only share them if you trust it.",
+                    &[],
+                ),
+                &gettextf("Share", &[]),
+                true,
             );
-            self.send_response(Ok(info));
+            glib::spawn_future_local(glib::clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                button,
+                async move {
+                    if confirmed.await {
+                        this.share(&button);
+                    }
+                }
+            ));
+        }
+
+        fn share(&self, button: &gtk::Button) {
+            // Rendering needs the widget, but encoding the picture can happen off the main loop.
+            let texture = self.render_picture();
+            let remembered = if self.system_picture.get() {
+                None
+            } else {
+                self.avatar.custom_image().and_downcast::<gdk::Texture>()
+            };
+            button.set_sensitive(false);
+
+            glib::spawn_future_local(glib::clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    let uri = gio::spawn_blocking(move || {
+                        let uri = save_picture(&texture, &glib::tmp_dir())?;
+                        remember_picture(remembered.as_ref(), &remembered_picture_path());
+                        Ok(uri)
+                    })
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(XdppError::Internal(String::from("Unable to save picture")).into())
+                    });
+
+                    match uri {
+                        Ok(uri) => {
+                            let username = this.username_row.text();
+                            let info = UserInformation::new(
+                                shared_username(this.share_username_row.is_active(), &username),
+                                &this.name_row.text(),
+                                uri,
+                            );
+                            this.send_response(Ok(info));
+                        }
+                        Err(error) => this.show_share_error(error),
+                    }
+                }
+            ));
+        }
+
+        /// Render the avatar for sharing, independent of the scale of the window.
+        pub fn render_picture(&self) -> gdk::Texture {
+            self.avatar
+                .draw_to_texture(picture_scale(self.avatar.size()))
+        }
+
+        /// Let the user know that sharing failed, then pass the error on to the app.
+        fn show_share_error(&self, error: PortalError) {
+            let dialog = adw::AlertDialog::new(
+                Some(&gettextf("Unable to Share Details", &[])),
+                Some(&gettextf("The profile picture could not be saved.", &[])),
+            );
+            dialog.add_response("close", &gettextf("Close", &[]));
+            let error = Cell::new(Some(error));
+            dialog.connect_response(
+                None,
+                glib::clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    move |_, _| {
+                        if let Some(error) = error.take() {
+                            this.send_response(Err(error));
+                        }
+                    }
+                ),
+            );
+            dialog.present(Some(self.obj().as_ref()));
         }
 
         #[template_callback]
         fn on_del_avatar_clicked(&self, _button: &gtk::Button) {
             self.avatar.set_custom_image(gdk::Paintable::NONE);
             self.del_btn.set_visible(false);
+            self.set_system_picture(false);
+        }
+
+        #[template_callback]
+        fn on_reset_avatar_clicked(&self, _button: &gtk::Button) {
+            self.load_system_picture();
+        }
+
+        fn set_system_picture(&self, system_picture: bool) {
+            self.system_picture.set(system_picture);
+            self.reset_btn.set_visible(!system_picture);
+        }
+
+        fn load_system_picture(&self) {
+            self.set_system_picture(true);
+            let busy = self.busy.start(&self.avatar_spinner, &*self.share_btn);
+            glib::spawn_future_local(glib::clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    let _busy = busy;
+                    let file = system_picture().await;
+                    // The user may have picked another picture in the meantime.
+                    if !this.system_picture.get() {
+                        return;
+                    }
+
+                    if let Some(file) = file {
+                        this.load_avatar_from_uri(&file.uri());
+                    } else {
+                        glib::g_debug!(LOG_DOMAIN, "No system picture found");
+                        this.avatar.set_custom_image(gdk::Paintable::NONE);
+                        this.del_btn.set_visible(false);
+                    }
+                }
+            ));
         }
 
-        pub fn load_avatar_from_file(&self, file: &gio::File) {
-            let texture = gdk::Texture::from_file(file).ok();
-            self.avatar.set_custom_image(texture.as_ref());
-            self.del_btn.set_visible(texture.is_some());
+        /// Load the picture shared last time if there is one, otherwise the system one.
+        pub fn load_picture(&self) {
+            let path = remembered_picture_path();
+            if path.is_file() {
+                self.load_avatar_from_uri(&gio::File::for_path(&path).uri());
+                self.set_system_picture(false);
+            } else {
+                self.load_system_picture();
+            }
+        }
+
+        /// Show the picture at `uri` on the avatar, or the initials if it can not be loaded.
+        pub fn load_avatar_from_uri(&self, uri: &str) {
+            let file = gio::File::for_uri(uri);
+            let cache = cached_picture_path(uri);
+            let cancellable = self.cancellable.borrow().clone();
+            let busy = self.busy.start(&self.avatar_spinner, &*self.share_btn);
+            glib::spawn_future_local(glib::clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    let _busy = busy;
+                    let texture = load_cached_texture(&file, &cache, &cancellable).await;
+                    // The window is going away, so leave it as it is.
+                    if cancellable.is_cancelled() {
+                        return;
+                    }
+                    this.avatar.set_custom_image(texture.as_ref());
+                    this.del_btn.set_visible(texture.is_some());
+                }
+            ));
+        }
+
+        #[template_callback]
+        fn on_avatar_drop(
+            &self,
+            value: &glib::Value,
+            _x: f64,
+            _y: f64,
+            _target: &gtk::DropTarget,
+        ) -> bool {
+            let Ok(file) = value.get::<gio::File>() else {
+                return false;
+            };
+            if !is_image(&file) {
+                glib::g_debug!(LOG_DOMAIN, "Ignoring drop of {}", file.uri());
+                return false;
+            }
+
+            self.load_avatar_from_uri(&file.uri());
+            self.set_system_picture(false);
+            true
         }
 
         #[template_callback]
@@ -140,21 +586,15 @@ mod imp {
                         }
 
                         let file = result.unwrap();
-                        this.load_avatar_from_file(&file);
+                        this.load_avatar_from_uri(&file.uri());
+                        this.set_system_picture(false);
                     },
                 ),
             );
         }
 
         fn send_response(&self, response: Result<UserInformation>) {
-            let sender = self.sender.take();
-            if let Some(sender) = sender {
-                if sender.send(response).is_err() {
-                    glib::g_critical!(LOG_DOMAIN, "Unable to send response through sender");
-                }
-            } else {
-                glib::g_critical!(LOG_DOMAIN, "Sender is not available");
-            }
+            self.reply.send(response);
             self.obj().close();
         }
     }
@@ -189,36 +629,40 @@ impl Responder for AccountWindow {
         {
             let imp = self.imp();
 
-            let mut home = glib::home_dir();
-            home.push(FACE_FILE);
-            imp.load_avatar_from_file(&gio::File::for_path(home.as_path()));
-            imp.avatar.set_text(glib::real_name().as_os_str().to_str());
+            imp.load_picture();
 
+            imp.unknown_app_banner
+                .set_revealed(is_unknown_app(&application));
+
+            // Requests of several apps can be open at once, so tell them apart by the app.
             let app_name = get_application_name(&application);
-            let desc = match app_name {
-                Some(app_name) => gettextf("{} requests your information.", &[&app_name]),
+            let desc = match &app_name {
+                Some(app_name) => {
+                    imp.window_title.set_subtitle(app_name);
+                    self.set_title(Some(&gettextf("Share Details With {}?", &[app_name])));
+                    gettextf("{} requests your information.", &[app_name])
+                }
                 None => gettextf("An app requests your information.", &[]),
             };
-            imp.desc_row.set_subtitle(desc.as_str());
+            imp.desc_row.set_subtitle(&escape_markup(&desc));
+            self.update_property(&[gtk::accessible::Property::Description(&desc)]);
 
-            let reason = options.reason().unwrap_or_default();
+            let reason = format_reason(options.reason().unwrap_or_default());
             if reason.is_empty() {
                 imp.reason_row.set_visible(false);
             }
-            imp.reason_row.set_subtitle(reason);
+            imp.reason_row.set_subtitle(&escape_markup(&reason));
 
             imp.username_row
                 .set_text(glib::user_name().as_os_str().to_str().unwrap());
+            // The avatar follows the name, so that its initials match what gets shared.
             imp.name_row
                 .set_text(glib::real_name().as_os_str().to_str().unwrap());
 
-            imp.sender.set(Some(sender));
+            imp.reply.set(sender);
 
-            if let Some(identifier) = application.window_identifier {
-                identifier.set_parent_of(self);
-            } else {
-                glib::g_warning!(LOG_DOMAIN, "Application does not have window identifier");
-            }
+            // The Account interface has no `modal` option, so the default applies.
+            parent_window(self, &application, None);
 
             self.present();
         } else {
@@ -228,6 +672,181 @@ impl Responder for AccountWindow {
     }
 
     fn cancel(&self) {
+        self.imp().cancellable.borrow().cancel();
         self.close();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::responders::test_utils;
+
+    #[test]
+    fn test_is_unknown_app() {
+        assert!(is_unknown_app(&test_utils::application()));
+    }
+
+    #[test]
+    fn test_is_image() {
+        assert!(is_image(&gio::File::for_path("/tmp/photo.png")));
+        assert!(!is_image(&gio::File::for_path("/tmp/notes.txt")));
+    }
+
+    #[test]
+    fn test_format_reason() {
+        assert_eq!(format_reason("To show your name"), "To show your name");
+        assert_eq!(format_reason(""), "");
+        assert_eq!(format_reason("To show\nyour\tname"), "To show your name");
+        assert_eq!(
+            format_reason("To show\u{7}\u{1b} your name\n"),
+            "To show your name"
+        );
+
+        let reason = format_reason(&"a".repeat(500));
+        assert_eq!(reason.chars().count(), MAX_REASON_LENGTH + 1);
+        assert!(reason.ends_with('…'));
+    }
+
+    #[test]
+    fn test_shared_username() {
+        assert_eq!(shared_username(true, "user"), "user");
+        assert_eq!(shared_username(false, "user"), "");
+
+        let image = Url::parse("file:///tmp/avatar.png").unwrap();
+        let info = UserInformation::new(shared_username(false, "user"), "User", image);
+        assert_eq!(info.id(), "");
+        let dict = test_utils::to_dict(&info);
+        assert_eq!(<&str>::try_from(&*dict["id"]).unwrap(), "");
+        assert_eq!(<&str>::try_from(&*dict["name"]).unwrap(), "User");
+    }
+
+    #[test]
+    fn test_picture_scale() {
+        assert_eq!(picture_scale(120), 4);
+        assert_eq!(picture_scale(100), 5);
+        assert_eq!(picture_scale(PICTURE_SIZE * 2), 1);
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_render_picture_size() {
+        test_utils::run(|| {
+            let window = AccountWindow::new();
+            window.present();
+            let texture = window.imp().render_picture();
+            assert_eq!(texture.width(), PICTURE_SIZE);
+            assert_eq!(texture.height(), PICTURE_SIZE);
+            window.close();
+        });
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_avatar_follows_name() {
+        test_utils::run(|| {
+            let window = AccountWindow::new();
+            let imp = window.imp();
+            imp.name_row.set_text("Ada Lovelace");
+            assert_eq!(imp.avatar.text().as_deref(), Some("Ada Lovelace"));
+
+            // A picture stays in place, as only the initials come from the name.
+            let bytes = glib::Bytes::from_static(&[0; 4]);
+            let texture = gdk::MemoryTexture::new(1, 1, gdk::MemoryFormat::R8g8b8a8, &bytes, 4);
+            imp.avatar.set_custom_image(Some(&texture));
+            imp.name_row.set_text("Grace Hopper");
+            assert_eq!(imp.avatar.text().as_deref(), Some("Grace Hopper"));
+            assert!(imp.avatar.custom_image().is_some());
+        });
+    }
+
+    #[test]
+    fn test_remember_picture() {
+        let bytes = glib::Bytes::from_static(&[0; 4]);
+        let texture = gdk::MemoryTexture::new(1, 1, gdk::MemoryFormat::R8g8b8a8, &bytes, 4);
+        let directory = glib::mkdtemp(glib::tmp_dir().join("xdpp-test-XXXXXX")).unwrap();
+        let path = directory.join(STATE_DIR).join(PICTURE_FILE);
+
+        remember_picture(Some(texture.upcast_ref()), &path);
+        assert!(path.is_file());
+        remember_picture(None, &path);
+        assert!(!path.exists());
+        remember_picture(None, &path);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_load_texture_cancelled() {
+        let bytes = glib::Bytes::from_static(&[0; 4]);
+        let texture = gdk::MemoryTexture::new(1, 1, gdk::MemoryFormat::R8g8b8a8, &bytes, 4);
+        let directory = glib::mkdtemp(glib::tmp_dir().join("xdpp-test-XXXXXX")).unwrap();
+        let path = directory.join("picture.png");
+        texture.save_to_png(&path).unwrap();
+        let file = gio::File::for_path(&path);
+
+        let context = glib::MainContext::default();
+        let cancellable = gio::Cancellable::new();
+        let loaded = context.block_on(load_texture(&file, &cancellable));
+        assert_eq!(loaded.map(|texture| texture.width()), Some(1));
+
+        cancellable.cancel();
+        assert!(context
+            .block_on(load_texture(&file, &cancellable))
+            .is_none());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_load_cached_texture() {
+        let bytes = glib::Bytes::from_static(&[0; 4]);
+        let texture = gdk::MemoryTexture::new(1, 1, gdk::MemoryFormat::R8g8b8a8, &bytes, 4);
+        let directory = glib::mkdtemp(glib::tmp_dir().join("xdpp-test-XXXXXX")).unwrap();
+        let path = directory.join("picture.png");
+        texture.save_to_png(&path).unwrap();
+        let uri = Url::from_file_path(&path).unwrap();
+        let cache = directory.join("cache.png");
+
+        let file = picture_file(uri.as_str()).unwrap();
+        assert!(!is_remote(&file));
+        let context = glib::MainContext::default();
+        let cancellable = gio::Cancellable::new();
+        let loaded = context.block_on(load_cached_texture(&file, &cache, &cancellable));
+        assert_eq!(loaded.map(|texture| texture.width()), Some(1));
+        // Local pictures are read in place.
+        assert!(!cache.exists());
+
+        assert!(picture_file(directory.join("missing.png").to_str().unwrap()).is_none());
+        assert!(is_remote(
+            &picture_file("https://phosh.mobi/avatar.png").unwrap()
+        ));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_save_picture_unique() {
+        let bytes = glib::Bytes::from_static(&[0; 4]);
+        let texture = gdk::MemoryTexture::new(1, 1, gdk::MemoryFormat::R8g8b8a8, &bytes, 4);
+        let directory = glib::mkdtemp(glib::tmp_dir().join("xdpp-test-XXXXXX")).unwrap();
+
+        // Two requests sharing at the same time must not overwrite each other.
+        let first = save_picture(texture.upcast_ref(), &directory).unwrap();
+        let second = save_picture(texture.upcast_ref(), &directory).unwrap();
+        assert_ne!(first, second);
+        assert!(first.to_file_path().unwrap().exists());
+        assert!(second.to_file_path().unwrap().exists());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_save_picture_failure() {
+        let bytes = glib::Bytes::from_static(&[0; 4]);
+        let texture = gdk::MemoryTexture::new(1, 1, gdk::MemoryFormat::R8g8b8a8, &bytes, 4);
+        let directory = glib::tmp_dir().join("xdpp-missing-directory");
+        let result = save_picture(texture.upcast_ref(), &directory);
+        assert!(matches!(result, Err(PortalError::Failed(_))));
+    }
+}
@@ -14,9 +14,12 @@ use gtk::glib::subclass::InitializingObject;
 use gtk::glib::Properties;
 use gtk::{gio, glib, CompositeTemplate, TemplateChild};
 
+use crate::utils::escape_markup;
+
 /*
  * `AppChooserRow` is used by `AppChooserWindow` to display an application representing given
- * `AppID`.
+ * `AppID`. The icon is looked up again when the icon theme of the display changes, so that it does
+ * not go stale when switching to a theme with a different icon set.
  */
 
 const LOG_DOMAIN: &str = "xdpp-app-chooser-row";
@@ -34,6 +37,8 @@ mod imp {
 
         #[template_child]
         image: TemplateChild<gtk::Image>,
+
+        icon_theme_handler: RefCell<Option<(gtk::IconTheme, glib::SignalHandlerId)>>,
     }
 
     #[glib::object_subclass]
@@ -52,7 +57,25 @@ mod imp {
     }
 
     #[glib::derived_properties]
-    impl ObjectImpl for AppChooserRow {}
+    impl ObjectImpl for AppChooserRow {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let icon_theme = gtk::IconTheme::for_display(&self.obj().display());
+            let handler = icon_theme.connect_changed(glib::clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.refresh_icon()
+            ));
+            *self.icon_theme_handler.borrow_mut() = Some((icon_theme, handler));
+        }
+
+        fn dispose(&self) {
+            if let Some((icon_theme, handler)) = self.icon_theme_handler.take() {
+                icon_theme.disconnect(handler);
+            }
+        }
+    }
 
     impl WidgetImpl for AppChooserRow {}
 
@@ -77,7 +100,7 @@ mod imp {
             let info = info.unwrap();
 
             let name = info.display_name();
-            self.obj().set_title(&name);
+            self.obj().set_title(&escape_markup(&name));
 
             if let Some(icon) = info.icon() {
                 self.image.set_from_gicon(&icon);
@@ -85,6 +108,26 @@ mod imp {
 
             *self.app_id.borrow_mut() = app_id;
         }
+
+        fn refresh_icon(&self) {
+            let app_id = self.app_id.borrow();
+            if app_id.is_empty() {
+                return;
+            }
+            let Some(info) = gio::DesktopAppInfo::new(&format!("{app_id}.desktop")) else {
+                glib::g_debug!(LOG_DOMAIN, "app-id `{app_id}` lost its app-info");
+                return;
+            };
+            glib::g_debug!(
+                LOG_DOMAIN,
+                "Icon theme changed, refreshing icon of `{app_id}`"
+            );
+            if let Some(icon) = info.icon() {
+                self.image.set_from_gicon(&icon);
+            } else {
+                self.image.set_icon_name(Some("application-x-executable"));
+            }
+        }
     }
 }
 
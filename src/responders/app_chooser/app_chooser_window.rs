@@ -6,33 +6,47 @@
  * Author: Arun Mani J <arun.mani@tether.to>
  */
 
-use std::cell::{Cell, RefCell};
-use std::ffi::OsStr;
-use std::str::FromStr;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use std::time::Duration;
 
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 use ashpd::backend::app_chooser::{Choice, DesktopID};
 use ashpd::backend::Result;
-use ashpd::{AppID, PortalError};
+use ashpd::url::Url;
+use ashpd::PortalError;
 use gtk::glib::subclass::InitializingObject;
 use gtk::{gio, glib, CompositeTemplate, TemplateChild};
-use tokio::sync::oneshot::Sender;
 
 use super::AppChooserRow;
-use crate::utils::gettextf;
-use crate::{Request, Responder};
+use crate::defaults::app_chooser_modal;
+use crate::utils::{escape_markup, gettextf, parent_window, parse_app_id, Busy};
+use crate::{Reply, Request, Responder, XdppError};
 
 /*
  * `AppChooserWindow` handles the AppChooser interface. It shows a dialog which displays the list of
  * appplications that can open the given URI. Users are also given option to launch Software to
  * search for a better application.
+ *
+ * The frontend already opens the default app without asking when it can, so a request here means
+ * the user is to choose. Still, without a last choice, the default app for the target, like the
+ * browser for `https` URIs, is listed first and selected.
+ *
+ * Apps may leave out the content type of a `file` URI. It is then sniffed from the file in the
+ * background, and the choices are sorted again once it is known.
  */
 
 const LOG_DOMAIN: &str = "xdpp-app-chooser-window";
 
 const GNOME_SOFTWARE: &str = "gnome-software";
+/// How long to watch Software after launching it. Exiting with an error by then counts as a failed
+/// launch, while still running means it started fine.
+const SOFTWARE_WATCH_TIME: Duration = Duration::from_secs(3);
 const MAX_LOCATION_LENGTH: usize = 100;
+const FALLBACK_CONTENT_TYPE: &str = "application/octet-stream";
 
 fn ellipsize_middle(text: &str, length: usize) -> String {
     if text.len() <= length {
@@ -43,6 +57,143 @@ fn ellipsize_middle(text: &str, length: usize) -> String {
     format!("{}…{}", &text[..half], &text[text.len() - half..])
 }
 
+/// Describe the content type in words, like "PNG image", which makes a better search term than the
+/// MIME type. Types unknown to the system are kept as is.
+fn describe_content_type(content_type: &str) -> String {
+    if gio::content_type_is_unknown(content_type) {
+        return content_type.to_string();
+    }
+
+    let description = gio::content_type_get_description(content_type);
+    if description.is_empty() {
+        content_type.to_string()
+    } else {
+        description.to_string()
+    }
+}
+
+/// The arguments to open Software with. It searches for apps handling the content type if known
+/// and shows the overview otherwise.
+fn software_argv(content_type: Option<&str>) -> Vec<OsString> {
+    let argument = match content_type {
+        Some(content_type) => format!("--search={}", describe_content_type(content_type)),
+        None => String::from("--mode=overview"),
+    };
+    vec![OsString::from(GNOME_SOFTWARE), OsString::from(argument)]
+}
+
+/// The modality of the chooser. The request's choice wins over the default of app choosers, and
+/// without either the default of all dialogs applies.
+fn chooser_modal(requested: Option<bool>, chooser_default: Option<bool>) -> Option<bool> {
+    requested.or(chooser_default)
+}
+
+/// Whether the app is still installed. A last choice may refer to an app removed since.
+fn is_installed(app_id: &str) -> bool {
+    gio::DesktopAppInfo::new(&format!("{app_id}.desktop")).is_some()
+}
+
+/// Where to look up the default app for what is being opened. Files, including `file` URIs, go by
+/// their content type and other URIs, like `https` or `mailto` ones, by their scheme.
+#[derive(Debug, PartialEq, Eq)]
+enum DefaultLookup {
+    ContentType(String),
+    Scheme(String),
+}
+
+fn default_lookup(
+    uri: Option<&Url>,
+    filename: Option<&str>,
+    content_type: Option<&str>,
+) -> Option<DefaultLookup> {
+    if let Some(uri) = uri.filter(|uri| uri.scheme() != "file") {
+        return Some(DefaultLookup::Scheme(uri.scheme().to_string()));
+    }
+    if let Some(content_type) = content_type {
+        return Some(DefaultLookup::ContentType(content_type.to_string()));
+    }
+    let path = filename
+        .map(PathBuf::from)
+        .or_else(|| uri.and_then(|uri| uri.to_file_path().ok()))?;
+    let (content_type, _) = gio::content_type_guess(Some(path), None);
+    Some(DefaultLookup::ContentType(content_type.to_string()))
+}
+
+/// The file to sniff the content type of, when the app left it out. Only `file` URIs can be read.
+fn sniff_target(uri: Option<&Url>, content_type: Option<&str>) -> Option<gio::File> {
+    if content_type.is_some() {
+        return None;
+    }
+    uri.filter(|uri| uri.scheme() == "file")
+        .map(|uri| gio::File::for_uri(uri.as_str()))
+}
+
+/// Sniff the content type of `file` from its contents, falling back to
+/// [`FALLBACK_CONTENT_TYPE`] when the file can not be read. Returns `None` if cancelled.
+async fn sniff_content_type(file: &gio::File, cancellable: &gio::Cancellable) -> Option<String> {
+    let query = file.query_info_future(
+        gio::FILE_ATTRIBUTE_STANDARD_CONTENT_TYPE.as_str(),
+        gio::FileQueryInfoFlags::NONE,
+        glib::Priority::DEFAULT,
+    );
+    let info = gio::CancellableFuture::new(query, cancellable.clone())
+        .await
+        .ok()?;
+    let content_type = info
+        .map_err(|error| glib::g_debug!(LOG_DOMAIN, "Unable to sniff {}: {error}", file.uri()))
+        .ok()
+        .and_then(|info| info.content_type())
+        .map_or_else(|| String::from(FALLBACK_CONTENT_TYPE), String::from);
+    Some(content_type)
+}
+
+/// The id of the default app, without the `.desktop` suffix like the choices.
+fn default_app_id(lookup: &DefaultLookup) -> Option<String> {
+    let app_info = match lookup {
+        DefaultLookup::ContentType(content_type) => {
+            gio::AppInfo::default_for_type(content_type, false)
+        }
+        DefaultLookup::Scheme(scheme) => gio::AppInfo::default_for_uri_scheme(scheme),
+    }?;
+    let app_id = app_info.id()?;
+    Some(app_id.trim_end_matches(".desktop").to_string())
+}
+
+/// The command line the app is launched with, for the record. D-Bus activatable apps or ones
+/// without a desktop file may have none.
+fn app_commandline(app_id: &str) -> Option<String> {
+    let info = gio::DesktopAppInfo::new(&format!("{app_id}.desktop"))?;
+    info.commandline()
+        .map(|commandline| commandline.to_string_lossy().into_owned())
+}
+
+fn supports_content_type(app_id: &str, content_type: &str) -> bool {
+    let Some(info) = gio::DesktopAppInfo::new(&format!("{app_id}.desktop")) else {
+        return false;
+    };
+
+    info.supported_types()
+        .iter()
+        .any(|supported| gio::content_type_is_a(content_type, supported))
+}
+
+/// Order the app-ids so that the ones supporting the content type come first. The relative order
+/// within each group is kept. Each app-id is paired with whether it is supported.
+fn sort_by_support<F>(app_ids: Vec<String>, supports: F) -> Vec<(String, bool)>
+where
+    F: Fn(&str) -> bool,
+{
+    let (supported, unsupported): (Vec<_>, Vec<_>) = app_ids
+        .into_iter()
+        .map(|app_id| {
+            let is_supported = supports(&app_id);
+            (app_id, is_supported)
+        })
+        .partition(|(_, is_supported)| *is_supported);
+
+    supported.into_iter().chain(unsupported).collect()
+}
+
 mod imp {
     #[allow(clippy::wildcard_imports)]
     use super::*;
@@ -60,11 +211,22 @@ mod imp {
         pub list_box: TemplateChild<gtk::ListBox>,
         #[template_child]
         pub status_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        pub software_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub software_spinner: TemplateChild<gtk::Spinner>,
 
         pub last_choice: RefCell<String>,
         pub content_type: RefCell<Option<String>>,
+        /// The valid choices of the request, kept to sort them again once the content type is
+        /// sniffed.
+        pub choices: RefCell<Vec<String>>,
+        pub cancellable: RefCell<gio::Cancellable>,
+
+        /// Launching Software keeps the dialog busy, so that it is not launched twice.
+        pub busy: Busy,
 
-        pub sender: Cell<Option<Sender<Result<Choice>>>>,
+        pub reply: Reply<Choice>,
     }
 
     #[glib::object_subclass]
@@ -83,7 +245,16 @@ mod imp {
         }
     }
 
-    impl ObjectImpl for AppChooserWindow {}
+    impl ObjectImpl for AppChooserWindow {
+        fn constructed(&self) {
+            self.parent_constructed();
+            *self.cancellable.borrow_mut() = gio::Cancellable::new();
+        }
+
+        fn dispose(&self) {
+            self.cancellable.borrow().cancel();
+        }
+    }
 
     impl WidgetImpl for AppChooserWindow {}
 
@@ -116,23 +287,51 @@ mod imp {
 
         #[template_callback]
         fn on_open_software_clicked(&self, _button: &gtk::Button) {
-            let mut args: Vec<&OsStr> = vec![&OsStr::new(GNOME_SOFTWARE)];
-            let content_type = self.content_type.borrow();
-            let search_term = format!("--search={}", content_type.as_ref().map_or("", |v| v));
-            if content_type.is_some() {
-                args.push(OsStr::new(&search_term));
-            } else {
-                args.push(OsStr::new("--mode=overview"));
+            let argv = software_argv(self.content_type.borrow().as_deref());
+            let argv: Vec<&OsStr> = argv.iter().map(OsString::as_os_str).collect();
+
+            let launcher = gio::SubprocessLauncher::new(gio::SubprocessFlags::NONE);
+            let context = WidgetExt::display(&*self.obj()).app_launch_context();
+            // Let the compositor focus Software once its window shows up.
+            if let Some(token) = context.startup_notify_id(None::<&gio::AppInfo>, &[]) {
+                launcher.setenv("XDG_ACTIVATION_TOKEN", token.as_str(), true);
+                launcher.setenv("DESKTOP_STARTUP_ID", token.as_str(), true);
             }
+            let subprocess = match launcher.spawn(&argv) {
+                Ok(subprocess) => subprocess,
+                Err(error) => {
+                    self.show_software_error(error.message());
+                    return;
+                }
+            };
 
-            if let Err(error) = gio::Subprocess::newv(&args[..], gio::SubprocessFlags::NONE) {
-                let dialog = adw::AlertDialog::new(
-                    Some(&gettextf("Failed to launch GNOME Software", &[])),
-                    Some(error.message()),
-                );
-                dialog.add_response("close", &gettextf("Close", &[]));
-                dialog.present(Some(self.obj().as_ref()));
-            }
+            // Software may exist but fail right away, which spawning alone does not tell.
+            let busy = self.busy.start(&self.software_spinner, &*self.software_btn);
+            glib::spawn_future_local(glib::clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    let _busy = busy;
+                    let exit = glib::future_with_timeout(
+                        SOFTWARE_WATCH_TIME,
+                        subprocess.wait_check_future(),
+                    )
+                    .await;
+                    if let Ok(Err(error)) = exit {
+                        glib::g_warning!(LOG_DOMAIN, "Software failed to start: {error}");
+                        this.show_software_error(error.message());
+                    }
+                }
+            ));
+        }
+
+        fn show_software_error(&self, message: &str) {
+            let dialog = adw::AlertDialog::new(
+                Some(&gettextf("Failed to launch GNOME Software", &[])),
+                Some(message),
+            );
+            dialog.add_response("close", &gettextf("Close", &[]));
+            dialog.present(Some(self.obj().as_ref()));
         }
 
         fn send_app_id(&self) {
@@ -144,51 +343,156 @@ mod imp {
             let row = row.unwrap();
 
             let app_id_str = row.dynamic_cast_ref::<AppChooserRow>().unwrap().app_id();
-            let app_id = AppID::from_str(&app_id_str);
 
-            if let Ok(app_id) = app_id {
-                let choice = Choice::new(app_id);
-                self.send_response(Ok(choice));
-            } else {
-                glib::g_critical!(LOG_DOMAIN, "Invalid app-id `{app_id_str}` on selected row");
-                let error = PortalError::Failed(String::from("Internal error"));
-                self.send_response(Err(error));
+            match parse_app_id(&app_id_str) {
+                Ok(app_id) => {
+                    if let Some(commandline) = app_commandline(&app_id_str) {
+                        glib::g_debug!(
+                            LOG_DOMAIN,
+                            "Chose `{app_id_str}`, launched as `{commandline}`"
+                        );
+                    } else {
+                        glib::g_debug!(LOG_DOMAIN, "Chose `{app_id_str}`, without a command line");
+                    }
+                    let choice = Choice::new(app_id);
+                    self.send_response(Ok(choice));
+                }
+                Err(error) => {
+                    glib::g_warning!(LOG_DOMAIN, "Invalid app-id `{app_id_str}` on selected row");
+                    self.send_response(Err(error));
+                }
             }
         }
 
-        fn send_response(&self, response: Result<Choice>) {
-            let sender = self.sender.take();
-            if let Some(sender) = sender {
-                if sender.send(response).is_err() {
-                    glib::g_critical!(LOG_DOMAIN, "Unable to send response through sender");
+        pub fn is_pending(&self) -> bool {
+            self.reply.is_pending()
+        }
+
+        pub fn send_response(&self, response: Result<Choice>) {
+            self.cancellable.borrow().cancel();
+            self.reply.send(response);
+            self.obj().close();
+        }
+
+        /// Sniff the content type of `file` and sort the choices by it once known. Without a last
+        /// choice, the default app of the sniffed type is put first.
+        pub fn sniff_content_type(&self, file: gio::File) {
+            let cancellable = self.cancellable.borrow().clone();
+            glib::spawn_future_local(glib::clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    let Some(content_type) = sniff_content_type(&file, &cancellable).await else {
+                        return;
+                    };
+                    glib::g_debug!(LOG_DOMAIN, "Sniffed {content_type} for {}", file.uri());
+                    if this.last_choice.borrow().is_empty() {
+                        let lookup = DefaultLookup::ContentType(content_type.clone());
+                        let default = default_app_id(&lookup)
+                            .filter(|app_id| this.choices.borrow().contains(app_id));
+                        if let Some(app_id) = default {
+                            *this.last_choice.borrow_mut() = app_id;
+                        }
+                    }
+                    *this.content_type.borrow_mut() = Some(content_type);
+                    this.show_choices();
                 }
-            } else {
-                glib::g_critical!(LOG_DOMAIN, "Sender is not available");
+            ));
+        }
+
+        pub fn rows(&self) -> Vec<AppChooserRow> {
+            let mut rows = Vec::new();
+            let mut index = 0;
+            while let Some(row) = self.list_box.row_at_index(index) {
+                rows.push(row.downcast::<AppChooserRow>().unwrap());
+                index += 1;
             }
-            self.obj().close();
+            rows
         }
 
+        /// Show the given choices. Rows of apps that are already listed are kept, so that streamed
+        /// updates neither flicker nor lose the selection.
         pub fn update_choices(&self, choices: Vec<DesktopID>) {
-            self.list_box.remove_all();
+            *self.choices.borrow_mut() = choices
+                .into_iter()
+                .map(|desktop_id| desktop_id.to_string())
+                .filter(|app_id| parse_app_id(app_id).is_ok())
+                .collect();
+            self.show_choices();
+        }
 
+        fn show_choices(&self) {
             let last_app_id = self.last_choice.borrow();
 
+            let app_ids: Vec<String> = self
+                .choices
+                .borrow()
+                .iter()
+                .filter(|app_id| *last_app_id != **app_id)
+                .cloned()
+                .collect();
+
+            let mut app_ids = if let Some(content_type) = self.content_type.borrow().as_deref() {
+                sort_by_support(app_ids, |app_id| {
+                    supports_content_type(app_id, content_type)
+                })
+            } else {
+                app_ids.into_iter().map(|app_id| (app_id, true)).collect()
+            };
+
             if !last_app_id.is_empty() {
-                let row = AppChooserRow::from_app_id(&last_app_id);
-                self.list_box.append(&row);
+                app_ids.insert(0, (last_app_id.clone(), true));
             }
 
-            for desktop_id in choices {
-                let app_id = desktop_id.to_string();
-                if *last_app_id == app_id {
-                    continue;
+            let selected = self
+                .list_box
+                .selected_row()
+                .and_downcast::<AppChooserRow>()
+                .map(|row| row.app_id());
+
+            let mut existing: HashMap<String, AppChooserRow> = HashMap::new();
+            for row in self.rows() {
+                if app_ids.iter().any(|(app_id, _)| *app_id == row.app_id()) {
+                    existing.insert(row.app_id(), row);
+                } else {
+                    self.list_box.remove(&row);
                 }
-                let row = AppChooserRow::from_app_id(&app_id);
-                self.list_box.append(&row);
             }
 
-            let page_name = if let Some(row) = self.list_box.row_at_index(0) {
+            for (index, (app_id, is_supported)) in app_ids.iter().enumerate() {
+                let position = i32::try_from(index).unwrap();
+                let row = if let Some(row) = existing.remove(app_id) {
+                    if row.index() != position {
+                        self.list_box.remove(&row);
+                        self.list_box.insert(&row, position);
+                    }
+                    row
+                } else {
+                    let row = AppChooserRow::from_app_id(app_id);
+                    self.list_box.insert(&row, position);
+                    row
+                };
+
+                if *is_supported {
+                    row.remove_css_class("dim-label");
+                } else {
+                    row.add_css_class("dim-label");
+                }
+            }
+
+            let selected_row = selected
+                .and_then(|app_id| {
+                    self.rows()
+                        .into_iter()
+                        .find(|row| row.app_id() == app_id)
+                        .map(Cast::upcast::<gtk::ListBoxRow>)
+                })
+                .or_else(|| self.list_box.row_at_index(0));
+
+            let page_name = if let Some(row) = selected_row {
                 self.list_box.select_row(Some(&row));
+                // The list scrolls within the window, so bring the selection into view.
+                row.grab_focus();
                 "list"
             } else {
                 self.open_but.set_sensitive(false);
@@ -250,28 +554,64 @@ impl Responder for AppChooserWindow {
                 }
                 return;
             }
-            imp.prefs_group.set_description(Some(&prefs_desc));
-            imp.status_page.set_description(Some(&status_desc));
-
-            *imp.last_choice.borrow_mut() = if let Some(desktop_id) = options.last_choice() {
-                desktop_id.to_string()
-            } else {
-                String::new()
+            imp.prefs_group
+                .set_description(Some(&escape_markup(&prefs_desc)));
+            imp.status_page
+                .set_description(Some(&escape_markup(&status_desc)));
+            // Screen readers do not pick up the group and page descriptions on their own.
+            imp.list_box
+                .update_property(&[gtk::accessible::Property::Description(&prefs_desc)]);
+            imp.status_page
+                .update_property(&[gtk::accessible::Property::Description(&status_desc)]);
+
+            let default_choice = || {
+                let lookup = default_lookup(uri, filename, options.content_type())?;
+                let app_id = default_app_id(&lookup)?;
+                glib::g_debug!(LOG_DOMAIN, "Default app for {lookup:?} is {app_id}");
+                choices
+                    .iter()
+                    .any(|choice| choice.to_string() == app_id)
+                    .then_some(app_id)
             };
+            *imp.last_choice.borrow_mut() = options
+                .last_choice()
+                .map(ToString::to_string)
+                .filter(|app_id| {
+                    let installed = is_installed(app_id);
+                    if !installed {
+                        glib::g_debug!(LOG_DOMAIN, "Last choice {app_id} is not installed");
+                    }
+                    installed
+                })
+                .or_else(default_choice)
+                .filter(|app_id| parse_app_id(app_id).is_ok())
+                .unwrap_or_default();
             *imp.content_type.borrow_mut() = options.content_type().map(String::from);
             imp.update_choices(choices);
-            imp.sender.set(Some(sender));
-
-            if let Some(identifier) = application.window_identifier {
-                identifier.set_parent_of(self);
+            imp.reply.set(sender);
+            if let Some(file) = sniff_target(uri, options.content_type()) {
+                imp.sniff_content_type(file);
             }
-            self.set_modal(options.modal().unwrap_or(false));
+
+            parent_window(
+                self,
+                &application,
+                chooser_modal(options.modal(), app_chooser_modal()),
+            );
 
             self.present();
         } else if let Request::AppChooserUpdateChoices { choices, sender } = request {
             let imp = self.imp();
-            imp.update_choices(choices);
-            if sender.send(Ok(())).is_err() {
+            // The choices belong to the request this window was opened for. Once that request has
+            // been answered or cancelled, there's nothing left to update.
+            let response = if imp.is_pending() {
+                imp.update_choices(choices);
+                Ok(())
+            } else {
+                glib::g_debug!(LOG_DOMAIN, "Ignoring update for a finished request");
+                Err(XdppError::ResponderMissing(String::from("No active request to update")).into())
+            };
+            if sender.send(response).is_err() {
                 glib::g_critical!(LOG_DOMAIN, "Unable to send response through sender");
             }
         } else {
@@ -281,6 +621,243 @@ impl Responder for AppChooserWindow {
     }
 
     fn cancel(&self) {
-        self.close();
+        let error = PortalError::Cancelled(String::from("Cancelled by application"));
+        self.imp().send_response(Err(error));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ashpd::zvariant::Value;
+    use tokio::sync::oneshot;
+
+    use super::*;
+    use crate::responders::test_utils;
+
+    #[test]
+    fn test_sort_by_support() {
+        let app_ids = vec![
+            String::from("org.gnome.TextEditor"),
+            String::from("org.gnome.Loupe"),
+            String::from("org.gnome.Papers"),
+            String::from("org.gnome.Snapshot"),
+        ];
+        let sorted = sort_by_support(app_ids, |app_id| {
+            app_id == "org.gnome.Loupe" || app_id == "org.gnome.Snapshot"
+        });
+        assert_eq!(
+            sorted,
+            vec![
+                (String::from("org.gnome.Loupe"), true),
+                (String::from("org.gnome.Snapshot"), true),
+                (String::from("org.gnome.TextEditor"), false),
+                (String::from("org.gnome.Papers"), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_app_commandline() {
+        assert_eq!(app_commandline("mobi.phosh.NonExistent"), None);
+    }
+
+    #[test]
+    fn test_describe_content_type() {
+        assert_eq!(describe_content_type("text/plain"), "plain text document");
+    }
+
+    #[test]
+    fn test_default_lookup() {
+        let lookup = |uri: &str| default_lookup(Some(&Url::parse(uri).unwrap()), None, None);
+        let scheme = |scheme: &str| Some(DefaultLookup::Scheme(String::from(scheme)));
+        let content_type =
+            |content_type: &str| Some(DefaultLookup::ContentType(String::from(content_type)));
+
+        assert_eq!(lookup("https://phosh.mobi"), scheme("https"));
+        assert_eq!(lookup("http://phosh.mobi"), scheme("http"));
+        assert_eq!(lookup("mailto:user@phosh.mobi"), scheme("mailto"));
+        assert_eq!(lookup("file:///tmp/notes.txt"), content_type("text/plain"));
+
+        let uri = Url::parse("file:///tmp/notes").unwrap();
+        assert_eq!(
+            default_lookup(Some(&uri), None, Some("image/png")),
+            content_type("image/png")
+        );
+        assert_eq!(
+            default_lookup(None, Some("/tmp/notes.txt"), None),
+            content_type("text/plain")
+        );
+        assert_eq!(default_lookup(None, None, None), None);
+    }
+
+    #[test]
+    fn test_sniff_content_type() {
+        let uri = Url::parse("file:///tmp/notes.txt").unwrap();
+        assert!(sniff_target(Some(&uri), None).is_some());
+        assert!(sniff_target(Some(&uri), Some("text/plain")).is_none());
+        let uri = Url::parse("https://phosh.mobi").unwrap();
+        assert!(sniff_target(Some(&uri), None).is_none());
+
+        let dir = glib::mkdtemp("xdpp-sniff-XXXXXX").unwrap();
+        // Without an extension, only the contents tell that it is a PNG.
+        let path = dir.join("picture");
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let cancellable = gio::Cancellable::new();
+        let context = glib::MainContext::new();
+        let sniff = |path: &std::path::Path| {
+            context.block_on(sniff_content_type(&gio::File::for_path(path), &cancellable))
+        };
+        assert_eq!(sniff(&path).as_deref(), Some("image/png"));
+        assert_eq!(
+            sniff(&dir.join("missing")).as_deref(),
+            Some(FALLBACK_CONTENT_TYPE)
+        );
+        cancellable.cancel();
+        assert_eq!(sniff(&path), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_software_argv() {
+        assert_eq!(
+            software_argv(Some("text/plain")),
+            ["gnome-software", "--search=plain text document"]
+        );
+        assert_eq!(software_argv(None), ["gnome-software", "--mode=overview"]);
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_update_after_cancel() {
+        test_utils::run(|| {
+            let window = AppChooserWindow::new();
+            let (sender, mut receiver) = oneshot::channel();
+            window.respond(Request::AppChooserChooseApplication {
+                application: test_utils::application(),
+                choices: Vec::new(),
+                options: test_utils::options(&[("uri", Value::from("https://phosh.mobi"))]),
+                sender,
+            });
+
+            window.cancel();
+            assert!(matches!(
+                receiver.try_recv(),
+                Ok(Err(PortalError::Cancelled(_)))
+            ));
+
+            let result = test_utils::respond(&window, |sender| Request::AppChooserUpdateChoices {
+                choices: Vec::new(),
+                sender,
+            });
+            assert!(matches!(result, Err(PortalError::NotFound(_))));
+        });
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_update_keeps_shared_rows() {
+        test_utils::run(|| {
+            let window = AppChooserWindow::new();
+            let imp = window.imp();
+
+            let choices: Vec<DesktopID> =
+                test_utils::from_value(&vec!["org.gnome.TextEditor", "org.gnome.Loupe"]);
+            imp.update_choices(choices);
+            let loupe = imp.list_box.row_at_index(1).unwrap();
+
+            let choices: Vec<DesktopID> =
+                test_utils::from_value(&vec!["org.gnome.Loupe", "org.gnome.Papers"]);
+            imp.update_choices(choices);
+            let app_ids: Vec<String> = imp.rows().iter().map(AppChooserRow::app_id).collect();
+            assert_eq!(app_ids, vec!["org.gnome.Loupe", "org.gnome.Papers"]);
+            assert_eq!(imp.list_box.row_at_index(0).unwrap(), loupe);
+        });
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_bounded_size() {
+        test_utils::run(|| {
+            let app_ids: Vec<String> = (0..40).map(|i| format!("org.example.App{i}")).collect();
+            let context = glib::MainContext::default();
+            // Phone and tablet widths.
+            for width in [360, 1024] {
+                let window = AppChooserWindow::new();
+                window.set_default_size(width, -1);
+                let imp = window.imp();
+                imp.update_choices(test_utils::from_value(&app_ids));
+                window.present();
+
+                let start = std::time::Instant::now();
+                while imp.list_box.width() == 0 {
+                    assert!(
+                        start.elapsed().as_secs() < 5,
+                        "Window was not laid out in time"
+                    );
+                    context.iteration(false);
+                }
+                // Many apps scroll within the window rather than stretching it.
+                assert!(window.height() <= window.default_height());
+                assert!(imp.list_box.width() <= width);
+                // The preferences page caps how wide the list gets on large screens.
+                if width > 600 {
+                    assert!(imp.list_box.width() < width * 2 / 3);
+                }
+                window.close();
+            }
+        });
+    }
+
+    #[test]
+    fn test_chooser_modal() {
+        assert_eq!(chooser_modal(None, Some(true)), Some(true));
+        assert_eq!(chooser_modal(Some(false), Some(true)), Some(false));
+        assert_eq!(chooser_modal(Some(true), None), Some(true));
+        assert_eq!(chooser_modal(None, None), None);
+    }
+
+    #[test]
+    fn test_is_installed() {
+        assert!(!is_installed("org.example.Removed"));
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_stale_last_choice() {
+        test_utils::run(|| {
+            let window = AppChooserWindow::new();
+            let (sender, _receiver) = oneshot::channel();
+            window.respond(Request::AppChooserChooseApplication {
+                application: test_utils::application(),
+                choices: Vec::new(),
+                options: test_utils::options(&[
+                    ("uri", Value::from("https://phosh.mobi")),
+                    ("last_choice", Value::from("org.example.Removed")),
+                ]),
+                sender,
+            });
+
+            let imp = window.imp();
+            assert!(imp.last_choice.borrow().is_empty());
+            assert!(imp.rows().is_empty());
+            assert!(!imp.open_but.is_sensitive());
+            window.cancel();
+        });
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_respond_without_location() {
+        test_utils::run(|| {
+            let window = AppChooserWindow::new();
+            let result =
+                test_utils::respond(&window, |sender| Request::AppChooserChooseApplication {
+                    application: test_utils::application(),
+                    choices: Vec::new(),
+                    options: test_utils::options(&[]),
+                    sender,
+                });
+            assert!(matches!(result, Err(PortalError::InvalidArgument(_))));
+        });
     }
 }
@@ -0,0 +1,111 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use ashpd::PortalError;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gio, glib};
+
+use crate::{Reply, Request, Responder, XdppError};
+
+/*
+ * `Email` handles the Email interface. The requester already built the `mailto:` URI, so it is
+ * handed to the default mail app without a dialog of our own. The activation token of the app is
+ * passed on, so that the mail app can take the focus.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-email";
+
+mod imp {
+    #[allow(clippy::wildcard_imports)]
+    use super::*;
+
+    #[derive(Default)]
+    pub struct Email {
+        pub cancellable: gio::Cancellable,
+        pub reply: Reply<()>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Email {
+        const NAME: &'static str = "XdppEmail";
+        type Type = super::Email;
+        type ParentType = glib::Object;
+    }
+
+    impl ObjectImpl for Email {}
+}
+
+glib::wrapper! {
+pub struct Email(ObjectSubclass<imp::Email>);
+}
+
+impl Email {
+    #[must_use]
+    pub fn new() -> Self {
+        glib::Object::builder().build()
+    }
+
+    fn compose_email(&self, uri: &str, activation_token: Option<&str>) {
+        let imp = self.imp();
+
+        let context = gio::AppLaunchContext::new();
+        if let Some(activation_token) = activation_token {
+            context.setenv("XDG_ACTIVATION_TOKEN", activation_token);
+        }
+
+        glib::g_debug!(LOG_DOMAIN, "Launching {uri}");
+        gio::AppInfo::launch_default_for_uri_async(
+            uri,
+            Some(&context),
+            Some(&imp.cancellable),
+            glib::clone!(
+                #[weak]
+                imp,
+                move |result| {
+                    let response = result.map_err(|error| {
+                        glib::g_warning!(LOG_DOMAIN, "Unable to launch mail app: {error}");
+                        XdppError::Internal(String::from("Unable to launch mail app")).into()
+                    });
+                    imp.reply.send(response);
+                }
+            ),
+        );
+    }
+}
+
+impl Default for Email {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Responder for Email {
+    fn respond(&self, request: Request) {
+        if let Request::EmailComposeEmail {
+            application: _,
+            uri,
+            activation_token,
+            sender,
+        } = request
+        {
+            self.imp().reply.set(sender);
+            self.compose_email(&uri, activation_token.as_deref());
+        } else {
+            glib::g_critical!(LOG_DOMAIN, "Unknown request {request:#?}");
+            panic!();
+        }
+    }
+
+    fn cancel(&self) {
+        let imp = self.imp();
+        imp.cancellable.cancel();
+        let error = PortalError::Cancelled(String::from("Cancelled by application"));
+        imp.reply.send(Err(error));
+    }
+}
@@ -9,6 +9,7 @@
 use std::cell::Cell;
 use std::path::PathBuf;
 
+use adw::prelude::*;
 use ashpd::backend::file_chooser::{
     OpenFileOptions, SaveFileOptions, SaveFilesOptions, SelectedFiles,
 };
@@ -16,50 +17,104 @@ use ashpd::backend::Result;
 use ashpd::desktop::file_chooser::{Choice, FileFilter};
 use ashpd::url::Url;
 use ashpd::PortalError;
-use gtk::prelude::*;
+use gettextrs::ngettext;
 use gtk::subclass::prelude::*;
 use gtk::{gio, glib};
 use pfs::file_selector::{FileSelector, FileSelectorMode};
-use tokio::sync::oneshot::Sender;
 
-use crate::utils::gettextf;
-use crate::{Request, Responder};
+use crate::defaults::{shortcut_folders, xdg_default_folders};
+use crate::utils::{accept_label_or, gettextf, initial_choice, parent_window, resolve_choices};
+use crate::{Reply, Request, Responder, XdppError};
 
 /*
  * `FileChooser` handles the File Chooser interface. It uses Phosh File Selector to display a dialog
- * to let users choose files.
+ * to let users choose files. The last used folder of each mode is remembered in a state file and
+ * offered when the request does not specify a folder. Without one, the selector starts in the home
+ * folder, or in the XDG documents or downloads folder if `xdg_default_folders` is set. The
+ * configured `shortcut_folders` and mounted removable media, like SD cards, are offered as shortcuts
+ * through the `shortcuts` property, a list of folder URIs, when the selector has it.
  */
 
 const LOG_DOMAIN: &str = "xdpp-file-chooser";
 
+const COMPOUND_EXTENSIONS: [&str; 3] = [".tar.gz", ".tar.bz2", ".tar.xz"];
+
+const STATE_DIR: &str = "xdg-desktop-portal-phrosh";
+const STATE_FILE: &str = "file-chooser.ini";
+const STATE_GROUP: &str = "last-folder";
+
+const SHORTCUTS_PROPERTY: &str = "shortcuts";
+
 /// Split the string by extension.
 ///
-/// The extension is the substring from the first `.` to the end of the string. If the string starts
-/// with a `.`, then the extension is searched from the second `.`.
+/// The extension is the substring from the last `.` to the end of the string, unless the string
+/// ends with one of the known compound extensions like `.tar.gz`. A `.` at the start of the string
+/// marks a hidden file, not an extension.
 ///
 /// Example:
 /// ```ignore
+/// assert_eq!(split_ext("my.report.pdf"), ("my.report", ".pdf"));
 /// assert_eq!(split_ext(".foo.tar.gz"), (".foo", ".tar.gz"));
 /// ```
 fn split_ext(file_name: &str) -> (&str, &str) {
-    let mut idx = file_name.len();
-    let chars = file_name.chars();
-    for (i, ch) in chars.enumerate() {
-        if i != 0 && ch == '.' {
-            idx = i;
-            break;
+    for ext in COMPOUND_EXTENSIONS {
+        if file_name.len() <= ext.len() {
+            continue;
+        }
+
+        let idx = file_name.len() - ext.len();
+        if file_name.is_char_boundary(idx) && file_name[idx..].eq_ignore_ascii_case(ext) {
+            return file_name.split_at(idx);
         }
     }
 
-    let prefix = &file_name[..idx];
-    let suffix = &file_name[idx..];
+    match file_name.rfind('.') {
+        Some(idx) if idx != 0 => file_name.split_at(idx),
+        _ => (file_name, ""),
+    }
+}
+
+/// Make a file name suggested by an app safe to offer for saving.
+///
+/// Only the last path component is kept, so that the name cannot point at another folder, and
+/// control characters like newlines are dropped. Names that refer to a folder like `..` become
+/// empty.
+///
+/// Example:
+/// ```ignore
+/// assert_eq!(sanitize_filename("../notes\n.txt"), "notes.txt");
+/// ```
+fn sanitize_filename(name: &str) -> String {
+    let base = name.rsplit('/').next().unwrap_or_default();
+    let sanitized: String = base.chars().filter(|c| !c.is_control()).collect();
+    match sanitized.as_str() {
+        "." | ".." => String::new(),
+        _ => sanitized,
+    }
+}
 
-    (prefix, suffix)
+/// Where a file to save ends up, see [`get_unique_file_uri`].
+struct UniqueFile {
+    uri: Url,
+    /// Whether a counter was added to not overwrite an existing file.
+    collided: bool,
+    /// Whether the name from the app had to be sanitized.
+    sanitized: bool,
 }
 
-fn get_unique_file_uri(original: &str, directory: &gio::File) -> Url {
-    let (prefix, suffix) = split_ext(original);
-    let mut file = directory.child(original);
+/// Find a name for `original` in `directory` that does not overwrite an existing file, by
+/// appending a counter like ` (2)` to the name.
+///
+/// The name comes from the app, so it is sanitized first and a name like `../notes.txt` ends up
+/// as `notes.txt` in `directory`. Returns `None` if the file would still not be right inside
+/// `directory`.
+fn get_unique_file_uri(original: &str, directory: &gio::File) -> Option<UniqueFile> {
+    let mut name = sanitize_filename(original);
+    if name.is_empty() {
+        name = gettextf("Untitled", &[]);
+    }
+    let (prefix, suffix) = split_ext(&name);
+    let mut file = directory.child(&name);
     let mut count = 2;
 
     while file.query_exists(gio::Cancellable::NONE) {
@@ -68,8 +123,174 @@ fn get_unique_file_uri(original: &str, directory: &gio::File) -> Url {
         count += 1;
     }
 
-    let uri = file.uri();
-    Url::parse(&uri).unwrap()
+    if !file.has_parent(Some(directory)) {
+        glib::g_warning!(
+            LOG_DOMAIN,
+            "`{original}` would be saved outside of the folder"
+        );
+        return None;
+    }
+    Some(UniqueFile {
+        uri: Url::parse(&file.uri()).ok()?,
+        collided: count > 2,
+        sanitized: name != original,
+    })
+}
+
+/// Tell the user that some of the files to save got new names, since the app still sees its own.
+fn show_renamed_notice(renamed: u32) {
+    let body = ngettext(
+        "{} file was renamed to avoid overwriting an existing file.",
+        "{} files were renamed to avoid overwriting existing files.",
+        renamed,
+    )
+    .replacen("{}", &renamed.to_string(), 1);
+    let dialog = adw::AlertDialog::new(Some(&gettextf("Files Renamed", &[])), Some(&body));
+    dialog.add_response("close", &gettextf("Close", &[]));
+    dialog.present(None::<&gtk::Widget>);
+}
+
+fn mode_key(mode: FileSelectorMode) -> &'static str {
+    match mode {
+        FileSelectorMode::OpenFile => "open-file",
+        FileSelectorMode::SaveFile => "save-file",
+        FileSelectorMode::SaveFiles => "save-files",
+    }
+}
+
+fn state_path() -> PathBuf {
+    let mut path = glib::user_state_dir();
+    path.push(STATE_DIR);
+    path.push(STATE_FILE);
+    path
+}
+
+fn load_last_folder(mode: FileSelectorMode) -> Option<gio::File> {
+    let key_file = glib::KeyFile::new();
+    key_file
+        .load_from_file(state_path(), glib::KeyFileFlags::NONE)
+        .ok()?;
+    let uri = key_file.string(STATE_GROUP, mode_key(mode)).ok()?;
+
+    let folder = gio::File::for_uri(&uri);
+    let file_type = folder.query_file_type(gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE);
+    if file_type == gio::FileType::Directory {
+        Some(folder)
+    } else {
+        glib::g_debug!(LOG_DOMAIN, "Last folder `{uri}` is no longer available");
+        None
+    }
+}
+
+fn store_last_folder(mode: FileSelectorMode, folder: &gio::File) {
+    let path = state_path();
+    let key_file = glib::KeyFile::new();
+    // A missing or unreadable state file is simply replaced.
+    let _ = key_file.load_from_file(&path, glib::KeyFileFlags::KEEP_COMMENTS);
+    key_file.set_string(STATE_GROUP, mode_key(mode), &folder.uri());
+
+    if let Some(parent) = path.parent() {
+        if let Err(error) = std::fs::create_dir_all(parent) {
+            glib::g_warning!(LOG_DOMAIN, "Unable to create state directory: {error}");
+            return;
+        }
+    }
+
+    if let Err(error) = key_file.save_to_file(&path) {
+        glib::g_warning!(LOG_DOMAIN, "Unable to save last folder: {error}");
+    }
+}
+
+/// The root URIs of the mounted removable media.
+fn removable_mounts() -> Vec<String> {
+    gio::VolumeMonitor::get()
+        .mounts()
+        .into_iter()
+        .filter(|mount| {
+            !mount.is_shadowed()
+                && mount
+                    .drive()
+                    .is_some_and(|drive| drive.is_removable() || drive.is_media_removable())
+        })
+        .map(|mount| mount.root().uri().to_string())
+        .collect()
+}
+
+/// The URIs of the configured shortcut folders that exist. A missing folder, like an unmounted
+/// share, is skipped until it shows up again.
+fn existing_shortcuts(folders: &[PathBuf]) -> Vec<String> {
+    folders
+        .iter()
+        .filter(|folder| {
+            let is_dir = folder.is_dir();
+            if !is_dir {
+                glib::g_debug!(
+                    LOG_DOMAIN,
+                    "Skipping missing shortcut folder {}",
+                    folder.display()
+                );
+            }
+            is_dir
+        })
+        .map(|folder| gio::File::for_path(folder).uri().to_string())
+        .collect()
+}
+
+/// The error for a selector that succeeded without any URI. Saving several files needs the
+/// destination directory, so going without it is a failure rather than a cancel.
+fn empty_selection_error(mode: FileSelectorMode) -> PortalError {
+    match mode {
+        FileSelectorMode::OpenFile | FileSelectorMode::SaveFile => {
+            PortalError::Cancelled(String::from("Cancelled by user"))
+        }
+        FileSelectorMode::SaveFiles => {
+            glib::g_warning!(LOG_DOMAIN, "No destination directory selected");
+            PortalError::Failed(String::from("No destination directory"))
+        }
+    }
+}
+
+/// Keep the folder asked for by the app if it exists, so that the selector opens right in it.
+/// Otherwise fall back to the home folder.
+fn existing_folder(folder: gio::File) -> gio::File {
+    let file_type = folder.query_file_type(gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE);
+    if file_type == gio::FileType::Directory {
+        folder
+    } else {
+        glib::g_debug!(
+            LOG_DOMAIN,
+            "Folder `{}` does not exist, using home instead",
+            folder.uri()
+        );
+        gio::File::for_path(glib::home_dir())
+    }
+}
+
+/// The folder to start in when there is neither a folder from the app nor a remembered one. With
+/// `xdg` set, opening starts in the documents folder and saving in the downloads folder, if the
+/// user has them.
+fn fallback_folder(
+    mode: FileSelectorMode,
+    xdg: bool,
+    user_special_dir: impl Fn(glib::UserDirectory) -> Option<PathBuf>,
+) -> PathBuf {
+    let directory = match mode {
+        FileSelectorMode::OpenFile => Some(glib::UserDirectory::Documents),
+        FileSelectorMode::SaveFile => Some(glib::UserDirectory::Downloads),
+        FileSelectorMode::SaveFiles => None,
+    };
+    directory
+        .filter(|_| xdg)
+        .and_then(user_special_dir)
+        .filter(|path| path.is_dir())
+        .unwrap_or_else(glib::home_dir)
+}
+
+fn default_folder(mode: FileSelectorMode) -> gio::File {
+    load_last_folder(mode).unwrap_or_else(|| {
+        let folder = fallback_folder(mode, xdg_default_folders(), glib::user_special_dir);
+        gio::File::for_path(folder)
+    })
 }
 
 fn convert_file_filter(filter: &FileFilter) -> gtk::FileFilter {
@@ -87,28 +308,49 @@ fn convert_file_filter(filter: &FileFilter) -> gtk::FileFilter {
     gtk_filter
 }
 
-fn convert_filters(
+/// The filters to offer and the position of the one to activate. An app may give a current filter
+/// without a list, which is then the only filter offered. Without a current filter, or with one
+/// that is not in the list, no filter is active and all files show until the user picks one.
+fn offered_filters(
     current_filter: Option<&FileFilter>,
     filters: &[FileFilter],
-) -> (u32, gio::ListModel) {
-    let model = gio::ListStore::with_type(gtk::FileFilter::static_type());
-    let mut current_filter_pos = gtk::INVALID_LIST_POSITION;
-
-    for (i, filter) in filters.iter().enumerate() {
-        model.append(&convert_file_filter(filter));
-        if current_filter.is_some() && current_filter.unwrap() == filter {
-            current_filter_pos = i.try_into().unwrap();
+) -> (Option<usize>, Vec<FileFilter>) {
+    match current_filter {
+        Some(current_filter) if filters.is_empty() => (Some(0), vec![current_filter.clone()]),
+        Some(current_filter) => {
+            let position = filters.iter().position(|filter| filter == current_filter);
+            if position.is_none() {
+                glib::g_debug!(
+                    LOG_DOMAIN,
+                    "Current filter `{}` is not one of the filters",
+                    current_filter.label()
+                );
+            }
+            (position, filters.to_vec())
         }
+        None => (None, filters.to_vec()),
     }
+}
 
-    if filters.is_empty() {
-        if let Some(current_filter) = current_filter {
-            model.append(&convert_file_filter(current_filter));
-            current_filter_pos = 0;
-        }
+/// Offer the filters to the file selector. The position of the filter found active on reply indexes
+/// into the returned filters.
+fn convert_filters(
+    current_filter: Option<&FileFilter>,
+    filters: &[FileFilter],
+    props: &mut Vec<(&str, glib::Value)>,
+) -> Vec<FileFilter> {
+    let (position, filters) = offered_filters(current_filter, filters);
+    let model = gio::ListStore::with_type(gtk::FileFilter::static_type());
+    for filter in &filters {
+        model.append(&convert_file_filter(filter));
     }
+    let position = position
+        .and_then(|position| u32::try_from(position).ok())
+        .unwrap_or(gtk::INVALID_LIST_POSITION);
 
-    (current_filter_pos, model.into())
+    props.push(("current-filter", position.into()));
+    props.push(("filters", model.upcast::<gio::ListModel>().into()));
+    filters
 }
 
 fn convert_choices(choices: &[Choice]) -> glib::Variant {
@@ -118,7 +360,7 @@ fn convert_choices(choices: &[Choice]) -> glib::Variant {
             choice.id(),
             choice.label(),
             choice.pairs().to_variant(),
-            choice.initial_selection(),
+            initial_choice(choice),
         ));
     }
     choices_vec.to_variant()
@@ -129,64 +371,116 @@ fn handle_open_file(
     props: &mut Vec<(&str, glib::Value)>,
     filters: &mut Vec<FileFilter>,
 ) {
-    if let Some(accept_label) = options.accept_label() {
-        props.push(("accept-label", accept_label.into()));
+    let directory = options.directory().unwrap_or(false);
+    let default_label = if directory {
+        gettextf("Select", &[])
     } else {
-        props.push(("accept-label", gettextf("Open", &[]).into()));
+        gettextf("Open", &[])
+    };
+    let accept_label = accept_label_or(options.accept_label(), &default_label);
+    props.push(("accept-label", accept_label.into()));
+    props.push(("directory", directory.into()));
+
+    // Filters only apply to files, so they are not offered when selecting a folder.
+    if !directory {
+        filters.extend(convert_filters(
+            options.current_filter(),
+            options.filters(),
+            props,
+        ));
     }
 
-    props.push(("directory", options.directory().unwrap_or(false).into()));
-
-    let (current_filter, file_filters) =
-        convert_filters(options.current_filter(), options.filters());
-    props.push(("current_filter", current_filter.into()));
-    props.push(("filters", file_filters.into()));
-    filters.extend(options.filters().iter().map(std::borrow::ToOwned::to_owned));
-
     let choices = convert_choices(options.choices());
     props.push(("choices", choices.into()));
 
     if let Some(current_folder_path) = options.current_folder() {
-        let current_folder = gio::File::for_path(current_folder_path);
+        let current_folder = existing_folder(gio::File::for_path(current_folder_path));
         props.push(("current-folder", current_folder.into()));
     } else {
-        let current_folder = gio::File::for_path(glib::home_dir());
+        let current_folder = default_folder(FileSelectorMode::OpenFile);
         props.push(("current-folder", current_folder.into()));
     }
 }
 
+/// Register the chosen files with the recent manager, so that they show up in other apps.
+fn add_recent_files(manager: &gtk::RecentManager, uris: &[Url]) {
+    for uri in uris {
+        if !manager.add_item(uri.as_str()) {
+            glib::g_debug!(LOG_DOMAIN, "Unable to add `{uri}` to recent files");
+        }
+    }
+}
+
+fn collect_selected_files(
+    uris: &[Url],
+    directory: bool,
+    current_filter: Option<FileFilter>,
+    choices: Vec<(String, String)>,
+) -> SelectedFiles {
+    let mut files = SelectedFiles::default();
+
+    for uri in uris {
+        files = files.uri(uri.clone());
+    }
+
+    if !directory {
+        if let Some(current_filter) = current_filter {
+            files = files.current_filter(current_filter);
+        }
+    }
+
+    for (key, value) in choices {
+        files = files.choice(&key, &value);
+    }
+
+    files
+}
+
+/// Read the choices the user made from the `selected-choices` property of the file selector. A
+/// value of an unexpected type is ignored, so that the selected files can still be sent.
+fn selected_choices(variant: &glib::Variant) -> Vec<(String, String)> {
+    <Vec<(String, String)>>::from_variant(variant).unwrap_or_else(|| {
+        glib::g_warning!(
+            LOG_DOMAIN,
+            "Ignoring choices of unexpected type `{}`",
+            variant.type_()
+        );
+        Vec::new()
+    })
+}
+
 fn handle_save_file(
     options: &SaveFileOptions,
     props: &mut Vec<(&str, glib::Value)>,
     filters: &mut Vec<FileFilter>,
 ) {
-    if let Some(accept_label) = options.accept_label() {
-        props.push(("accept-label", accept_label.into()));
-    } else {
-        props.push(("accept-label", gettextf("Save", &[]).into()));
-    }
+    let accept_label = accept_label_or(options.accept_label(), &gettextf("Save", &[]));
+    props.push(("accept-label", accept_label.into()));
 
-    let (current_filter, file_filters) =
-        convert_filters(options.current_filter(), options.filters());
-    props.push(("current_filter", current_filter.into()));
-    props.push(("filters", file_filters.into()));
-    filters.extend(options.filters().iter().map(std::borrow::ToOwned::to_owned));
+    filters.extend(convert_filters(
+        options.current_filter(),
+        options.filters(),
+        props,
+    ));
 
     let choices = convert_choices(options.choices());
     props.push(("choices", choices.into()));
 
     if let Some(current_file_path) = options.current_file() {
         let current_file = gio::File::for_path(current_file_path);
-        let current_folder = current_file.parent();
-        let current_name = current_file.basename();
+        let current_folder = current_file.parent().map(existing_folder);
+        let current_name = current_file
+            .basename()
+            .map(|name| sanitize_filename(&name.to_string_lossy()));
         props.push(("current-folder", current_folder.into()));
         props.push(("filename", current_name.into()));
     } else if let Some(current_folder_path) = options.current_folder() {
-        let current_folder = gio::File::for_path(current_folder_path);
+        let current_folder = existing_folder(gio::File::for_path(current_folder_path));
+        let current_name = sanitize_filename(options.current_name().unwrap_or(""));
         props.push(("current-folder", current_folder.into()));
-        props.push(("filename", options.current_name().unwrap_or("").into()));
+        props.push(("filename", current_name.into()));
     } else {
-        let current_folder = gio::File::for_path(glib::home_dir());
+        let current_folder = default_folder(FileSelectorMode::SaveFile);
         props.push(("current-folder", current_folder.into()));
     }
 }
@@ -196,17 +490,14 @@ fn handle_save_files(
     props: &mut Vec<(&str, glib::Value)>,
     files: &mut Vec<PathBuf>,
 ) {
-    if let Some(accept_label) = options.accept_label() {
-        props.push(("accept-label", accept_label.into()));
-    } else {
-        props.push(("accept-label", gettextf("Save", &[]).into()));
-    }
+    let accept_label = accept_label_or(options.accept_label(), &gettextf("Save", &[]));
+    props.push(("accept-label", accept_label.into()));
 
     if let Some(current_folder_path) = options.current_folder() {
-        let current_folder = gio::File::for_path(current_folder_path);
+        let current_folder = existing_folder(gio::File::for_path(current_folder_path));
         props.push(("current-folder", current_folder.into()));
     } else {
-        let current_folder = gio::File::for_path(glib::home_dir());
+        let current_folder = default_folder(FileSelectorMode::SaveFiles);
         props.push(("current-folder", current_folder.into()));
     }
 
@@ -225,10 +516,12 @@ mod imp {
     #[derive(Default)]
     pub struct FileChooser {
         pub mode: Cell<Option<FileSelectorMode>>,
+        pub directory: Cell<bool>,
         pub filters: Cell<Vec<FileFilter>>,
+        pub choices: Cell<Vec<Choice>>,
         pub files: Cell<Vec<PathBuf>>,
         pub window: Cell<Option<FileSelector>>,
-        pub sender: Cell<Option<Sender<Result<SelectedFiles>>>>,
+        pub reply: Reply<SelectedFiles>,
     }
 
     #[glib::object_subclass]
@@ -242,6 +535,13 @@ mod imp {
 
     impl FileChooser {
         pub fn on_file_selector_done(&self, success: bool) {
+            // The selector may signal more than once, but only the first one is answered. Later
+            // ones must neither reply again nor remember the folder or recent files.
+            if !self.reply.is_pending() {
+                glib::g_debug!(LOG_DOMAIN, "Ignoring done of an answered request");
+                return;
+            }
+
             if !success {
                 let error = PortalError::Cancelled(String::from("Cancelled by user"));
                 self.send_response(Err(error));
@@ -250,7 +550,7 @@ mod imp {
 
             let Some(window) = self.window.take() else {
                 glib::g_critical!(LOG_DOMAIN, "No window available to take");
-                let error = PortalError::Failed(String::from("Internal error"));
+                let error = XdppError::Internal(String::from("Internal error")).into();
                 self.send_response(Err(error));
                 return;
             };
@@ -261,64 +561,84 @@ mod imp {
                 return;
             };
 
-            if uris.is_empty() {
-                let error = PortalError::Cancelled(String::from("Cancelled by user"));
-                self.send_response(Err(error));
-                return;
-            }
-
-            let mut files = SelectedFiles::default();
-
             let Some(mode) = self.mode.take() else {
                 glib::g_critical!(LOG_DOMAIN, "No mode available to take");
-                let error = PortalError::Failed(String::from("Internal error"));
+                let error = XdppError::Internal(String::from("Internal error")).into();
                 self.send_response(Err(error));
                 return;
             };
 
-            match mode {
+            if uris.is_empty() {
+                self.send_response(Err(empty_selection_error(mode)));
+                return;
+            }
+
+            let files = match mode {
                 FileSelectorMode::OpenFile | FileSelectorMode::SaveFile => {
-                    for uri in uris {
-                        let url = Url::parse(&uri).unwrap();
-                        files = files.uri(url);
+                    if let Some(folder) = gio::File::for_uri(&uris[0]).parent() {
+                        store_last_folder(mode, &folder);
                     }
 
+                    let selected: Vec<Url> =
+                        uris.iter().map(|uri| Url::parse(uri).unwrap()).collect();
+
                     let current_filter_pos: u32 = window.property("current-filter");
                     let mut filters = self.filters.take();
-                    if (current_filter_pos as usize) < filters.len() {
-                        let current_filter = filters.remove(current_filter_pos as usize);
-                        files = files.current_filter(current_filter);
-                    }
+                    let current_filter = if (current_filter_pos as usize) < filters.len() {
+                        Some(filters.remove(current_filter_pos as usize))
+                    } else {
+                        None
+                    };
 
                     let choices_variant: glib::Variant = window.property("selected-choices");
-                    let choices = <Vec<(String, String)>>::from_variant(&choices_variant).unwrap();
-                    for (key, value) in choices {
-                        files = files.choice(&key, &value);
+                    let choices =
+                        resolve_choices(&self.choices.take(), &selected_choices(&choices_variant));
+
+                    let directory = self.directory.take();
+                    if !directory {
+                        add_recent_files(&gtk::RecentManager::default(), &selected);
                     }
+                    collect_selected_files(&selected, directory, current_filter, choices)
                 }
                 FileSelectorMode::SaveFiles => {
+                    let mut files = SelectedFiles::default();
+                    let mut renamed = 0;
                     let directory = gio::File::for_uri(&uris[0]);
+                    store_last_folder(mode, &directory);
                     for file_name in self.files.take() {
-                        let os_str = file_name.as_os_str();
-                        let file_name_str = os_str.to_str().unwrap();
-                        let uri = get_unique_file_uri(file_name_str, &directory);
-                        files = files.uri(uri);
+                        let file_name_str = file_name.to_string_lossy();
+                        let Some(unique) = get_unique_file_uri(&file_name_str, &directory) else {
+                            let error = XdppError::InvalidRequest(format!(
+                                "Unable to save `{file_name_str}` in the folder"
+                            ));
+                            self.send_response(Err(error.into()));
+                            return;
+                        };
+                        if unique.collided || unique.sanitized {
+                            glib::g_debug!(
+                                LOG_DOMAIN,
+                                "Renamed `{file_name_str}` to `{}`",
+                                unique.uri
+                            );
+                        }
+                        // The notice is about not overwriting files, so only collisions count.
+                        if unique.collided {
+                            renamed += 1;
+                        }
+                        files = files.uri(unique.uri);
+                    }
+                    if renamed > 0 {
+                        show_renamed_notice(renamed);
                     }
+                    files
                 }
-            }
+            };
 
             self.send_response(Ok(files));
         }
 
         fn send_response(&self, response: Result<SelectedFiles>) {
-            let sender = self.sender.take();
-            if let Some(sender) = sender {
-                if sender.send(response).is_err() {
-                    glib::g_critical!(LOG_DOMAIN, "Unable to send response through sender");
-                }
-            } else {
-                glib::g_critical!(LOG_DOMAIN, "Sender is not available");
-            }
+            self.reply.send(response);
         }
     }
 }
@@ -347,8 +667,10 @@ impl Responder for FileChooser {
         let sender;
         let mode;
         let modal;
+        let mut directory = false;
         let mut props = Vec::new();
         let mut filters = Vec::new();
+        let mut choices = Vec::new();
         let mut files = Vec::new();
 
         if let Request::FileChooserOpenFile {
@@ -363,8 +685,10 @@ impl Responder for FileChooser {
             mode = FileSelectorMode::OpenFile;
             props.push(("mode", mode.into()));
             props.push(("title", title.into()));
-            modal = options.modal().unwrap_or(true);
+            modal = options.modal();
+            directory = options.directory().unwrap_or(false);
             handle_open_file(&options, &mut props, &mut filters);
+            choices = options.choices().to_vec();
         } else if let Request::FileChooserSaveFile {
             application: application_in,
             title,
@@ -377,8 +701,9 @@ impl Responder for FileChooser {
             mode = FileSelectorMode::SaveFile;
             props.push(("mode", mode.into()));
             props.push(("title", title.into()));
-            modal = options.modal().unwrap_or(true);
+            modal = options.modal();
             handle_save_file(&options, &mut props, &mut filters);
+            choices = options.choices().to_vec();
         } else if let Request::FileChooserSaveFiles {
             application: application_in,
             title,
@@ -391,7 +716,7 @@ impl Responder for FileChooser {
             mode = FileSelectorMode::SaveFiles;
             props.push(("mode", mode.into()));
             props.push(("title", title.into()));
-            modal = options.modal().unwrap_or(true);
+            modal = options.modal();
             handle_save_files(&options, &mut props, &mut files);
         } else {
             glib::g_critical!(LOG_DOMAIN, "Unknown request {request:#?}");
@@ -399,6 +724,17 @@ impl Responder for FileChooser {
         }
 
         let window = FileSelector::new();
+
+        let mut shortcuts = existing_shortcuts(&shortcut_folders());
+        shortcuts.extend(removable_mounts());
+        if !shortcuts.is_empty() {
+            if window.find_property(SHORTCUTS_PROPERTY).is_some() {
+                props.push((SHORTCUTS_PROPERTY, shortcuts.into()));
+            } else {
+                glib::g_debug!(LOG_DOMAIN, "File selector does not support shortcuts");
+            }
+        }
+
         window.set_properties_from_value(&props);
 
         let imp = self.imp();
@@ -413,20 +749,17 @@ impl Responder for FileChooser {
             ),
         );
 
-        if let Some(identifier) = application.window_identifier {
-            identifier.set_parent_of(&window);
-        } else {
-            glib::g_warning!(LOG_DOMAIN, "Application does not have window identifier");
-        }
-        window.set_modal(modal);
+        parent_window(&window, &application, modal);
 
         window.present();
 
         imp.mode.set(Some(mode));
+        imp.directory.set(directory);
         imp.filters.set(filters);
+        imp.choices.set(choices);
         imp.files.set(files);
         imp.window.set(Some(window));
-        imp.sender.set(Some(sender));
+        imp.reply.set(sender);
     }
 
     fn cancel(&self) {
@@ -443,11 +776,19 @@ impl Responder for FileChooser {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::responders::test_utils;
 
     #[test]
     fn test_split_ext() {
         assert_eq!(split_ext("foo.txt"), ("foo", ".txt"));
         assert_eq!(split_ext("foo.tar.gz"), ("foo", ".tar.gz"));
+        assert_eq!(split_ext("foo.tar.xz"), ("foo", ".tar.xz"));
+        assert_eq!(split_ext("foo.TAR.BZ2"), ("foo", ".TAR.BZ2"));
+        assert_eq!(split_ext("notes.md"), ("notes", ".md"));
+        assert_eq!(
+            split_ext("my.project.final.pdf"),
+            ("my.project.final", ".pdf")
+        );
         assert_eq!(split_ext("foo."), ("foo", "."));
         assert_eq!(split_ext("foo"), ("foo", ""));
         assert_eq!(split_ext(".foo"), (".foo", ""));
@@ -455,4 +796,215 @@ mod test {
         assert_eq!(split_ext(".foo.tar.gz"), (".foo", ".tar.gz"));
         assert_eq!(split_ext(".foo.txt"), (".foo", ".txt"));
     }
+
+    #[test]
+    fn test_existing_shortcuts() {
+        let directory = glib::mkdtemp(glib::tmp_dir().join("xdpp-test-XXXXXX")).unwrap();
+        let missing = directory.join("missing");
+        let shortcuts = existing_shortcuts(&[missing, directory.clone()]);
+        assert_eq!(
+            shortcuts,
+            [gio::File::for_path(&directory).uri().to_string()]
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_offered_filters() {
+        let images = FileFilter::new("Images").mimetype("image/*");
+        let documents = FileFilter::new("Documents").mimetype("application/pdf");
+        let text = FileFilter::new("Text").glob("*.txt");
+        let filters = [images.clone(), documents.clone(), text.clone()];
+
+        let (position, offered) = offered_filters(Some(&documents), &filters);
+        assert_eq!(position, Some(1));
+        assert_eq!(offered[1], documents);
+        let (position, offered) = offered_filters(Some(&text), &filters);
+        assert_eq!(offered[position.unwrap()], text);
+
+        // Without an active filter, all files show.
+        let (position, offered) = offered_filters(None, &filters);
+        assert_eq!(position, None);
+        assert_eq!(offered.len(), 3);
+        let other = FileFilter::new("Other").glob("*.md");
+        assert_eq!(offered_filters(Some(&other), &filters).0, None);
+
+        let (position, offered) = offered_filters(Some(&images), &[]);
+        assert_eq!(position, Some(0));
+        assert_eq!(offered, vec![images]);
+    }
+
+    #[test]
+    fn test_selected_choices() {
+        let variant = vec![(String::from("encoding"), String::from("utf8"))].to_variant();
+        assert_eq!(
+            selected_choices(&variant),
+            vec![(String::from("encoding"), String::from("utf8"))]
+        );
+        assert!(selected_choices(&"utf8".to_variant()).is_empty());
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("report.pdf"), "report.pdf");
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_filename("/tmp/photo.png"), "photo.png");
+        assert_eq!(sanitize_filename("folder/"), "");
+        assert_eq!(sanitize_filename(".."), "");
+        assert_eq!(sanitize_filename("."), "");
+        assert_eq!(sanitize_filename("..notes"), "..notes");
+        assert_eq!(sanitize_filename("line\nbreak.txt"), "linebreak.txt");
+        assert_eq!(sanitize_filename("tab\tand\u{1b}escape"), "tabandescape");
+    }
+
+    #[test]
+    fn test_get_unique_file_uri() {
+        let path = glib::mkdtemp(glib::tmp_dir().join("xdpp-test-XXXXXX")).unwrap();
+        let directory = gio::File::for_path(&path);
+        std::fs::write(path.join("notes.txt"), "").unwrap();
+
+        let unique = get_unique_file_uri("report.pdf", &directory).unwrap();
+        assert!(unique.uri.path().ends_with("/report.pdf"));
+        assert!(!unique.collided);
+        assert!(!unique.sanitized);
+
+        let unique = get_unique_file_uri("notes.txt", &directory).unwrap();
+        assert!(unique.uri.path().ends_with("/notes%20(2).txt"));
+        assert!(unique.collided);
+        assert!(!unique.sanitized);
+
+        // Names that point elsewhere stay inside the folder.
+        for name in ["../evil.txt", "/tmp/evil.txt", "sub/../../evil.txt"] {
+            let unique = get_unique_file_uri(name, &directory).unwrap();
+            let file = gio::File::for_uri(unique.uri.as_str());
+            assert!(
+                file.has_parent(Some(&directory)),
+                "{name} escaped to {}",
+                unique.uri
+            );
+            assert_eq!(file.basename().unwrap(), std::path::Path::new("evil.txt"));
+            assert!(unique.sanitized);
+            assert!(!unique.collided);
+        }
+        let unique = get_unique_file_uri("../notes.txt", &directory).unwrap();
+        assert!(unique.uri.path().ends_with("/notes%20(2).txt"));
+        assert!(unique.collided && unique.sanitized);
+
+        let unique = get_unique_file_uri("..", &directory).unwrap();
+        assert!(gio::File::for_uri(unique.uri.as_str()).has_parent(Some(&directory)));
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_collect_selected_files_directory() {
+        let folder = Url::parse("file:///home/user/Documents").unwrap();
+        let filter = FileFilter::new("Text").mimetype("text/plain");
+
+        let files = collect_selected_files(
+            std::slice::from_ref(&folder),
+            true,
+            Some(filter),
+            Vec::new(),
+        );
+        let dict = test_utils::to_dict(&files);
+        let uris = <Vec<String>>::try_from(dict["uris"].try_clone().unwrap()).unwrap();
+        assert_eq!(uris, vec![folder.to_string()]);
+        assert!(!dict.contains_key("current_filter"));
+    }
+
+    #[test]
+    fn test_empty_selection_error() {
+        assert!(matches!(
+            empty_selection_error(FileSelectorMode::OpenFile),
+            PortalError::Cancelled(_)
+        ));
+        assert!(matches!(
+            empty_selection_error(FileSelectorMode::SaveFile),
+            PortalError::Cancelled(_)
+        ));
+        assert!(matches!(
+            empty_selection_error(FileSelectorMode::SaveFiles),
+            PortalError::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn test_existing_folder() {
+        let folder = gio::File::for_path(glib::tmp_dir());
+        assert!(existing_folder(folder.clone()).equal(&folder));
+
+        let missing = gio::File::for_path("/nonexistent/xdpp/deep/folder");
+        let home = gio::File::for_path(glib::home_dir());
+        assert!(existing_folder(missing).equal(&home));
+    }
+
+    #[test]
+    fn test_fallback_folder() {
+        let documents = glib::tmp_dir();
+        let lookup = |directory| match directory {
+            glib::UserDirectory::Documents => Some(documents.clone()),
+            glib::UserDirectory::Downloads => Some(PathBuf::from("/nonexistent/xdpp/Downloads")),
+            _ => None,
+        };
+
+        let folder = fallback_folder(FileSelectorMode::OpenFile, true, lookup);
+        assert_eq!(folder, documents);
+        // A missing folder falls back to home, as does a mode without an XDG folder.
+        let folder = fallback_folder(FileSelectorMode::SaveFile, true, lookup);
+        assert_eq!(folder, glib::home_dir());
+        let folder = fallback_folder(FileSelectorMode::SaveFiles, true, lookup);
+        assert_eq!(folder, glib::home_dir());
+
+        let folder = fallback_folder(FileSelectorMode::OpenFile, false, lookup);
+        assert_eq!(folder, glib::home_dir());
+        let folder = fallback_folder(FileSelectorMode::OpenFile, true, |_| None);
+        assert_eq!(folder, glib::home_dir());
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_add_recent_files() {
+        test_utils::run(|| {
+            let directory = glib::mkdtemp(glib::tmp_dir().join("xdpp-test-XXXXXX")).unwrap();
+            let file = directory.join("recent.txt");
+            std::fs::write(&file, "").unwrap();
+            let uri = Url::from_file_path(&file).unwrap();
+
+            let manager = gtk::RecentManager::default();
+            add_recent_files(&manager, std::slice::from_ref(&uri));
+            // The manager looks the file up asynchronously before adding it.
+            let context = glib::MainContext::default();
+            let start = std::time::Instant::now();
+            while !manager.has_item(uri.as_str()) {
+                assert!(start.elapsed().as_secs() < 5, "File was not added in time");
+                context.iteration(false);
+            }
+
+            manager.remove_item(uri.as_str()).unwrap();
+            std::fs::remove_dir_all(directory).unwrap();
+        });
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_done_twice() {
+        test_utils::run(|| {
+            let chooser = FileChooser::new();
+            let imp = chooser.imp();
+            let (sender, mut receiver) = tokio::sync::oneshot::channel();
+            imp.reply.set(sender);
+            imp.window.set(Some(FileSelector::new()));
+            imp.mode.set(Some(FileSelectorMode::OpenFile));
+
+            // Nothing is selected, so the first done cancels.
+            imp.on_file_selector_done(true);
+            imp.on_file_selector_done(true);
+            assert!(matches!(
+                receiver.try_recv(),
+                Ok(Err(PortalError::Cancelled(_)))
+            ));
+            assert!(!imp.reply.is_pending());
+        });
+    }
 }
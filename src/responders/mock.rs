@@ -0,0 +1,140 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::path::Path;
+
+use ashpd::backend::access::AccessResponse;
+use ashpd::backend::app_chooser::Choice;
+use ashpd::backend::file_chooser::SelectedFiles;
+use ashpd::desktop::account::UserInformation;
+use ashpd::desktop::print::PreparePrint;
+use ashpd::url::Url;
+use ashpd::PortalError;
+use gtk::glib;
+
+use crate::{Request, Responder};
+
+/*
+ * `MockResponder` replies to every request right away with a canned result, without showing any
+ * window. It lets the D-Bus surface be exercised where there is no display, like in CI. The first
+ * app is chosen, files resolve to `$HOME/test.txt` and devices are granted as asked.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-mock";
+
+const MOCK_FILE: &str = "test.txt";
+const MOCK_PRINT_TOKEN: u32 = 1;
+
+fn home_uri(file_name: impl AsRef<Path>) -> Url {
+    Url::from_file_path(glib::home_dir().join(file_name)).unwrap()
+}
+
+fn send<T>(sender: tokio::sync::oneshot::Sender<ashpd::backend::Result<T>>, response: T) {
+    if sender.send(Ok(response)).is_err() {
+        glib::g_critical!(LOG_DOMAIN, "Unable to send response through sender");
+    }
+}
+
+#[derive(Default)]
+pub struct MockResponder;
+
+impl Responder for MockResponder {
+    fn respond(&self, request: Request) {
+        glib::g_debug!(LOG_DOMAIN, "Replying to {}", request.interface().name());
+        match request {
+            Request::AccessAccessDialog { sender, .. } => send(sender, AccessResponse::default()),
+            Request::AccountGetUserInformation { sender, .. } => {
+                let info = UserInformation::new(
+                    &glib::user_name().to_string_lossy(),
+                    &glib::real_name().to_string_lossy(),
+                    home_uri(".face"),
+                );
+                send(sender, info);
+            }
+            Request::AppChooserChooseApplication {
+                choices, sender, ..
+            } => {
+                let app_id = choices
+                    .iter()
+                    .find_map(|choice| choice.inner().as_ref().ok().cloned());
+                if let Some(app_id) = app_id {
+                    send(sender, Choice::new(app_id));
+                } else {
+                    let error = PortalError::Cancelled(String::from("No valid choices"));
+                    if sender.send(Err(error)).is_err() {
+                        glib::g_critical!(LOG_DOMAIN, "Unable to send response through sender");
+                    }
+                }
+            }
+            Request::AppChooserUpdateChoices { sender, .. }
+            | Request::EmailComposeEmail { sender, .. }
+            | Request::PrintPrint { sender, .. } => send(sender, ()),
+            Request::FileChooserOpenFile { sender, .. }
+            | Request::FileChooserSaveFile { sender, .. } => {
+                send(sender, SelectedFiles::default().uri(home_uri(MOCK_FILE)));
+            }
+            Request::FileChooserSaveFiles {
+                options, sender, ..
+            } => {
+                let files = options
+                    .files()
+                    .iter()
+                    .fold(SelectedFiles::default(), |files, file| {
+                        files.uri(home_uri(file))
+                    });
+                send(sender, files);
+            }
+            Request::PrintPreparePrint {
+                settings,
+                page_setup,
+                sender,
+                ..
+            } => {
+                let reply = PreparePrint {
+                    settings: *settings,
+                    page_setup: *page_setup,
+                    token: MOCK_PRINT_TOKEN,
+                };
+                send(sender, reply);
+            }
+            Request::UsbAcquireDevices {
+                devices, sender, ..
+            } => {
+                let grants = devices
+                    .into_iter()
+                    .map(|(id, _device, access)| (id, access))
+                    .collect();
+                send(sender, grants);
+            }
+        }
+    }
+
+    fn cancel(&self) {}
+}
+
+#[cfg(test)]
+mod test {
+    use ashpd::backend::file_chooser::OpenFileOptions;
+
+    use super::*;
+    use crate::responders::test_utils;
+
+    #[test]
+    fn test_open_file() {
+        let files = test_utils::respond(&MockResponder, |sender| Request::FileChooserOpenFile {
+            application: test_utils::application(),
+            title: String::from("Open"),
+            options: test_utils::options::<OpenFileOptions>(&[]),
+            sender,
+        })
+        .unwrap();
+        let dict = test_utils::to_dict(&files);
+        let uris = <Vec<String>>::try_from(dict["uris"].try_clone().unwrap()).unwrap();
+        assert_eq!(uris, vec![home_uri(MOCK_FILE).to_string()]);
+    }
+}
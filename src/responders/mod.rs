@@ -6,10 +6,82 @@
  * Author: Arun Mani J <arun.mani@tether.to>
  */
 
+mod access_window;
 mod account_window;
 mod app_chooser;
+mod email;
 mod file_chooser;
+#[cfg(feature = "mock")]
+mod mock;
+mod print;
+mod print_window;
+#[cfg(test)]
+pub(crate) mod test_utils;
+mod usb_window;
 
+use gtk::gio;
+
+use crate::Interface;
+
+pub use access_window::AccessWindow;
 pub use account_window::AccountWindow;
 pub use app_chooser::AppChooserWindow;
+pub use email::Email;
 pub use file_chooser::FileChooser;
+#[cfg(feature = "mock")]
+pub use mock::MockResponder;
+pub use print::Print;
+pub use usb_window::UsbWindow;
+
+/// The UI resources the responder of `interface` builds its windows from.
+#[must_use]
+pub fn templates(interface: Interface) -> &'static [&'static str] {
+    match interface {
+        Interface::Access => &["/mobi/phosh/xdpp/ui/access_window.ui"],
+        Interface::Account => &["/mobi/phosh/xdpp/ui/account_window.ui"],
+        Interface::AppChooser => &[
+            "/mobi/phosh/xdpp/ui/app_chooser_window.ui",
+            "/mobi/phosh/xdpp/ui/app_chooser_row.ui",
+        ],
+        Interface::Email | Interface::FileChooser => &[],
+        Interface::Print => &["/mobi/phosh/xdpp/ui/print_window.ui"],
+        Interface::Usb => &["/mobi/phosh/xdpp/ui/usb_window.ui"],
+    }
+}
+
+/// The first UI resource of `interface` missing from the registered resources. Binding a missing
+/// template panics deep inside GTK, so this is checked before a responder is built.
+#[must_use]
+pub fn missing_template(interface: Interface) -> Option<&'static str> {
+    templates(interface)
+        .iter()
+        .find(|path| gio::resources_get_info(path, gio::ResourceLookupFlags::NONE).is_err())
+        .copied()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_templates() {
+        let bundle = include_str!("../xdg-desktop-portal-phrosh.gresource.xml");
+        for interface in [
+            Interface::Access,
+            Interface::Account,
+            Interface::AppChooser,
+            Interface::Email,
+            Interface::FileChooser,
+            Interface::Print,
+            Interface::Usb,
+        ] {
+            for path in templates(interface) {
+                let file = path.strip_prefix("/mobi/phosh/xdpp/").unwrap();
+                assert!(
+                    bundle.contains(&format!(">{file}<")),
+                    "{file} is not bundled"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,468 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::os::fd::{AsFd, AsRawFd};
+use std::time::{Duration, Instant};
+
+use ashpd::backend::Result;
+use ashpd::desktop::print::{PageSetup, PreparePrint, Settings};
+use ashpd::zvariant::serialized::Context;
+use ashpd::zvariant::{to_bytes, OwnedFd, OwnedValue, Type, Value, LE};
+use ashpd::PortalError;
+use gtk::glib;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot::Sender;
+
+use super::print_window::PrintWindow;
+use crate::utils::{gettextf, parent_window};
+use crate::{Application, Reply, Request, Responder, XdppError};
+
+/*
+ * `Print` handles the Print interface. `PreparePrint` shows a `PrintWindow`, which fits the printer
+ * and the page setup into a single window that works on a phone. The chosen printer and setup are
+ * kept under a token, which `Print` later redeems to send the document to the printer.
+ *
+ * Tokens are random and only the app that prepared a print may redeem its token, so that apps can
+ * not guess and use up the prints of others. Apps without an app-id can not be told apart. Prints
+ * that are not redeemed in time are dropped.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-print";
+
+/// How long a prepared print waits for its `Print`.
+const PREPARED_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+struct PreparedPrint {
+    printer: gtk::Printer,
+    settings: gtk::PrintSettings,
+    page_setup: gtk::PageSetup,
+}
+
+struct Prepared<T> {
+    /// The app that prepared the print, the only one that may redeem it.
+    app_id: Option<String>,
+    since: Instant,
+    value: T,
+}
+
+/// Prepared prints by their token.
+struct Tokens<T>(HashMap<u32, Prepared<T>>);
+
+impl<T> Tokens<T> {
+    fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    /// Drop the entries older than [`PREPARED_LIFETIME`] at `now`.
+    fn expire(&mut self, now: Instant) {
+        self.0.retain(|token, prepared| {
+            let expired = now.duration_since(prepared.since) >= PREPARED_LIFETIME;
+            if expired {
+                glib::g_debug!(LOG_DOMAIN, "Dropping prepared print {token}, never printed");
+            }
+            !expired
+        });
+    }
+
+    /// Keep `value` for the app with `app_id` under a new, unused token, which is never 0.
+    fn insert(&mut self, app_id: Option<String>, value: T) -> u32 {
+        let now = Instant::now();
+        self.expire(now);
+        let token = loop {
+            let token = glib::random_int();
+            if token != 0 && !self.0.contains_key(&token) {
+                break token;
+            }
+        };
+        let prepared = Prepared {
+            app_id,
+            since: now,
+            value,
+        };
+        self.0.insert(token, prepared);
+        token
+    }
+
+    /// Redeem `token` for the app with `app_id`. The entry is kept if it belongs to another app.
+    fn take(&mut self, token: u32, app_id: Option<&str>) -> Option<T> {
+        self.expire(Instant::now());
+        if self.0.get(&token)?.app_id.as_deref() != app_id {
+            glib::g_warning!(LOG_DOMAIN, "Print token {token} belongs to another app");
+            return None;
+        }
+        self.0.remove(&token).map(|prepared| prepared.value)
+    }
+}
+
+thread_local! {
+    // Responders live only for one request, so the prepared prints outlive them here.
+    static PREPARED: RefCell<Tokens<PreparedPrint>> = RefCell::new(Tokens::new());
+}
+
+fn store_prepared(app_id: Option<String>, prepared: PreparedPrint) -> u32 {
+    PREPARED.with_borrow_mut(|tokens| tokens.insert(app_id, prepared))
+}
+
+fn take_prepared(token: Option<u32>, app_id: Option<&str>) -> Option<PreparedPrint> {
+    PREPARED.with_borrow_mut(|tokens| tokens.take(token?, app_id))
+}
+
+/// Convert a portal dictionary to the `a{sv}` variant understood by the GTK print objects.
+///
+/// Both sides use the same keys, so only the values need to be carried over.
+fn to_variant<T>(value: &T) -> glib::Variant
+where
+    T: Serialize + Type,
+{
+    let variant_dict = glib::VariantDict::new(None);
+    let ctxt = Context::new_dbus(LE, 0);
+    let dict: HashMap<String, OwnedValue> = match to_bytes(ctxt, value) {
+        Ok(data) => data.deserialize().map(|(dict, _)| dict).unwrap_or_default(),
+        Err(error) => {
+            glib::g_warning!(LOG_DOMAIN, "Unable to serialize dictionary: {error}");
+            HashMap::new()
+        }
+    };
+
+    for (key, value) in &dict {
+        match &**value {
+            Value::Str(string) => variant_dict.insert_value(key, &string.as_str().to_variant()),
+            Value::F64(number) => variant_dict.insert_value(key, &number.to_variant()),
+            value => glib::g_debug!(LOG_DOMAIN, "Ignoring {key} with value {value:?}"),
+        }
+    }
+
+    variant_dict.end()
+}
+
+/// Convert an `a{sv}` variant from the GTK print objects back to a portal dictionary.
+///
+/// Keys unknown to the portal, like the printer name, are dropped.
+fn from_variant<T>(variant: &glib::Variant) -> T
+where
+    T: for<'de> Deserialize<'de> + Type + Default,
+{
+    let entries = variant
+        .get::<HashMap<String, glib::Variant>>()
+        .unwrap_or_default();
+    let mut dict: HashMap<String, Value> = HashMap::new();
+
+    for (key, value) in entries {
+        if let Some(string) = value.str() {
+            dict.insert(key, Value::from(String::from(string)));
+        } else if let Some(number) = value.get::<f64>() {
+            dict.insert(key, Value::F64(number));
+        }
+    }
+
+    let ctxt = Context::new_dbus(LE, 0);
+    let result = to_bytes(ctxt, &dict).and_then(|data| data.deserialize().map(|(value, _)| value));
+    result.unwrap_or_else(|error| {
+        glib::g_warning!(LOG_DOMAIN, "Unable to deserialize {variant}: {error}");
+        T::default()
+    })
+}
+
+fn print_document(
+    application: &Application,
+    title: &str,
+    fd: OwnedFd,
+    token: Option<u32>,
+    sender: Sender<Result<()>>,
+) {
+    let send_response = |sender: Sender<Result<()>>, response| {
+        if sender.send(response).is_err() {
+            glib::g_critical!(LOG_DOMAIN, "Unable to send response through sender");
+        }
+    };
+
+    // Tokens are handed out by `PreparePrint` only and are valid for a single document.
+    let app_id = application.app_id.as_ref().map(ToString::to_string);
+    let Some(prepared) = take_prepared(token, app_id.as_deref()) else {
+        glib::g_warning!(LOG_DOMAIN, "Rejecting print with unknown token {token:?}");
+        let error = XdppError::InvalidRequest(String::from("Unknown print token")).into();
+        send_response(sender, Err(error));
+        return;
+    };
+
+    if !prepared.printer.accepts_pdf() {
+        let error = PortalError::Failed(String::from("Printer does not accept PDF documents"));
+        send_response(sender, Err(error));
+        return;
+    }
+
+    let job = gtk::PrintJob::new(
+        title,
+        &prepared.printer,
+        &prepared.settings,
+        &prepared.page_setup,
+    );
+    if let Err(error) = job.set_source_fd(fd.as_fd().as_raw_fd()) {
+        glib::g_critical!(LOG_DOMAIN, "Unable to read document: {error}");
+        let error = XdppError::Internal(String::from("Unable to read document")).into();
+        send_response(sender, Err(error));
+        return;
+    }
+
+    // The job reads from the descriptor until it is sent, so the closure keeps it open.
+    let sender = Cell::new(Some(sender));
+    job.send(move |_, result| {
+        let _ = &fd;
+        let Some(sender) = sender.take() else {
+            return;
+        };
+
+        if let Err(error) = result {
+            glib::g_critical!(LOG_DOMAIN, "Unable to send print job: {error}");
+            let error = XdppError::Internal(String::from("Unable to send print job")).into();
+            send_response(sender, Err(error));
+        } else {
+            send_response(sender, Ok(()));
+        }
+    });
+}
+
+mod imp {
+    #[allow(clippy::wildcard_imports)]
+    use super::*;
+
+    #[derive(Default)]
+    pub struct Print {
+        pub dialog: RefCell<Option<PrintWindow>>,
+        /// The app preparing the print, which the token is tied to.
+        pub app_id: RefCell<Option<String>>,
+        pub reply: Reply<PreparePrint>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Print {
+        const NAME: &'static str = "XdppPrint";
+        type Type = super::Print;
+        type ParentType = glib::Object;
+    }
+
+    impl ObjectImpl for Print {}
+
+    impl Print {
+        pub fn on_dialog_response(&self, response: gtk::ResponseType) {
+            // Closing the dialog on cancel responds as well, after the reply has been sent.
+            let Some(dialog) = self.dialog.take() else {
+                glib::g_debug!(LOG_DOMAIN, "Dialog already closed");
+                return;
+            };
+            let printer = dialog.selected_printer();
+            let settings = dialog.settings();
+            let page_setup = dialog.page_setup();
+            dialog.close();
+
+            if response != gtk::ResponseType::Ok {
+                let error = PortalError::Cancelled(String::from("Cancelled by user"));
+                self.send_response(Err(error));
+                return;
+            }
+
+            let Some(printer) = printer else {
+                let error = PortalError::Failed(String::from("No printer selected"));
+                self.send_response(Err(error));
+                return;
+            };
+
+            let reply_settings = from_variant(&settings.to_gvariant());
+            let reply_page_setup = from_variant(&page_setup.to_gvariant());
+
+            let token = store_prepared(
+                self.app_id.take(),
+                PreparedPrint {
+                    printer,
+                    settings,
+                    page_setup,
+                },
+            );
+            self.send_response(Ok(PreparePrint {
+                settings: reply_settings,
+                page_setup: reply_page_setup,
+                token,
+            }));
+        }
+
+        pub fn send_response(&self, response: Result<PreparePrint>) {
+            self.reply.send(response);
+        }
+    }
+}
+
+glib::wrapper! {
+pub struct Print(ObjectSubclass<imp::Print>);
+}
+
+impl Print {
+    #[must_use]
+    pub fn new() -> Self {
+        glib::Object::builder().build()
+    }
+
+    fn prepare_print(
+        &self,
+        application: &Application,
+        title: &str,
+        settings: &Settings,
+        page_setup: &PageSetup,
+        modal: Option<bool>,
+        accept_label: Option<&str>,
+    ) {
+        let title = if title.is_empty() {
+            gettextf("Print", &[])
+        } else {
+            String::from(title)
+        };
+
+        let dialog = PrintWindow::new(
+            &title,
+            &gtk::PrintSettings::from_gvariant(&to_variant(settings)),
+            &gtk::PageSetup::from_gvariant(&to_variant(page_setup)),
+            accept_label,
+        );
+
+        let imp = self.imp();
+        imp.app_id
+            .replace(application.app_id.as_ref().map(ToString::to_string));
+
+        dialog.connect_response(glib::clone!(
+            #[weak]
+            imp,
+            move |response| imp.on_dialog_response(response)
+        ));
+
+        parent_window(&dialog, application, modal);
+
+        dialog.present();
+
+        imp.dialog.replace(Some(dialog));
+    }
+}
+
+impl Default for Print {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Responder for Print {
+    fn respond(&self, request: Request) {
+        match request {
+            Request::PrintPreparePrint {
+                application,
+                title,
+                settings,
+                page_setup,
+                modal,
+                accept_label,
+                sender,
+            } => {
+                self.imp().reply.set(sender);
+                self.prepare_print(
+                    &application,
+                    &title,
+                    &settings,
+                    &page_setup,
+                    modal,
+                    accept_label.as_deref(),
+                );
+            }
+            Request::PrintPrint {
+                application,
+                title,
+                fd,
+                token,
+                sender,
+            } => print_document(&application, &title, fd, token, sender),
+            request => {
+                glib::g_critical!(LOG_DOMAIN, "Unknown request {request:#?}");
+                panic!();
+            }
+        }
+    }
+
+    fn cancel(&self) {
+        let imp = self.imp();
+        let dialog = imp.dialog.take();
+        if let Some(dialog) = dialog {
+            dialog.close();
+            let error = PortalError::Cancelled(String::from("Cancelled by application"));
+            imp.send_response(Err(error));
+        } else {
+            glib::g_debug!(LOG_DOMAIN, "No dialog available to close");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ashpd::desktop::print::Orientation;
+
+    use super::*;
+    use crate::responders::test_utils;
+
+    #[test]
+    fn test_page_setup_round_trip() {
+        let page_setup = PageSetup {
+            name: Some(String::from("iso_a4")),
+            width: Some(210.0),
+            height: Some(297.0),
+            orientation: Some(Orientation::Landscape),
+            ..PageSetup::default()
+        };
+
+        let variant = to_variant(&page_setup);
+        let page_setup: PageSetup = from_variant(&variant);
+        let dict = test_utils::to_dict(&page_setup);
+        assert_eq!(<&str>::try_from(&dict["Name"]).unwrap(), "iso_a4");
+        assert!((f64::try_from(&dict["Height"]).unwrap() - 297.0).abs() < f64::EPSILON);
+        assert_eq!(page_setup.orientation, Some(Orientation::Landscape));
+    }
+
+    #[test]
+    fn test_tokens() {
+        let mut tokens = Tokens::new();
+        let token = tokens.insert(Some(String::from("mobi.phosh.Test")), 1);
+        assert_ne!(token, 0);
+
+        // Another app can neither redeem nor use up the token.
+        assert_eq!(tokens.take(token, Some("mobi.phosh.Other")), None);
+        assert_eq!(tokens.take(token, None), None);
+        assert_eq!(tokens.take(token, Some("mobi.phosh.Test")), Some(1));
+        assert_eq!(tokens.take(token, Some("mobi.phosh.Test")), None);
+
+        let token = tokens.insert(None, 2);
+        tokens.expire(Instant::now() + PREPARED_LIFETIME);
+        assert_eq!(tokens.take(token, None), None);
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_print_unknown_token() {
+        test_utils::run(|| {
+            let responder = Print::new();
+            let fd = OwnedFd::from(std::os::fd::OwnedFd::from(
+                std::fs::File::open("/dev/null").unwrap(),
+            ));
+            let result = test_utils::respond(&responder, |sender| Request::PrintPrint {
+                application: test_utils::application(),
+                title: String::from("Document"),
+                fd,
+                token: Some(42),
+                sender,
+            });
+            assert!(matches!(result, Err(PortalError::InvalidArgument(_))));
+        });
+    }
+}
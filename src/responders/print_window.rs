@@ -0,0 +1,333 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::cell::{Cell, RefCell};
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::glib::subclass::InitializingObject;
+use gtk::{glib, CompositeTemplate, TemplateChild};
+
+/*
+ * `PrintWindow` is the dialog `Print` shows for `PreparePrint`. It fits a phone, with the printer,
+ * the copies and the page setup as rows, in place of the GTK print dialog which is too wide. Like
+ * the GTK dialog, it reports the outcome through a response and hands out the chosen printer,
+ * settings and page setup.
+ *
+ * Printers are looked up in the background and added as they are found. The printer of the
+ * settings, or else the default printer, is selected once it shows up.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-print-window";
+
+const ORIENTATIONS: [gtk::PageOrientation; 4] = [
+    gtk::PageOrientation::Portrait,
+    gtk::PageOrientation::Landscape,
+    gtk::PageOrientation::ReversePortrait,
+    gtk::PageOrientation::ReverseLandscape,
+];
+
+/// The position of `orientation` in the orientation row.
+fn orientation_position(orientation: gtk::PageOrientation) -> u32 {
+    ORIENTATIONS
+        .iter()
+        .position(|known| *known == orientation)
+        .and_then(|position| u32::try_from(position).ok())
+        .unwrap_or(0)
+}
+
+/// The orientation at `position` of the orientation row.
+fn orientation_at(position: u32) -> gtk::PageOrientation {
+    usize::try_from(position)
+        .ok()
+        .and_then(|position| ORIENTATIONS.get(position))
+        .copied()
+        .unwrap_or(gtk::PageOrientation::Portrait)
+}
+
+type ResponseCallback = Box<dyn Fn(gtk::ResponseType)>;
+
+mod imp {
+    #[allow(clippy::wildcard_imports)]
+    use super::*;
+
+    #[derive(CompositeTemplate, Default)]
+    #[template(resource = "/mobi/phosh/xdpp/ui/print_window.ui")]
+    pub struct PrintWindow {
+        #[template_child]
+        pub window_title: TemplateChild<adw::WindowTitle>,
+        #[template_child]
+        pub print_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub printer_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub printer_names: TemplateChild<gtk::StringList>,
+        #[template_child]
+        pub copies_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub paper_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub paper_names: TemplateChild<gtk::StringList>,
+        #[template_child]
+        pub orientation_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub top_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub bottom_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub left_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub right_row: TemplateChild<adw::SpinRow>,
+
+        pub printers: RefCell<Vec<gtk::Printer>>,
+        /// The printer to select once found, from the settings of the request.
+        pub preferred_printer: RefCell<Option<String>>,
+        pub paper_sizes: RefCell<Vec<gtk::PaperSize>>,
+        pub settings: RefCell<Option<gtk::PrintSettings>>,
+
+        pub on_response: RefCell<Option<ResponseCallback>>,
+        pub responded: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PrintWindow {
+        const NAME: &'static str = "XdppPrintWindow";
+        type Type = super::PrintWindow;
+        type ParentType = adw::Window;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for PrintWindow {}
+
+    impl WidgetImpl for PrintWindow {}
+
+    impl WindowImpl for PrintWindow {
+        fn close_request(&self) -> glib::Propagation {
+            self.respond(gtk::ResponseType::Cancel);
+            self.parent_close_request()
+        }
+    }
+
+    impl AdwWindowImpl for PrintWindow {}
+
+    #[gtk::template_callbacks]
+    impl PrintWindow {
+        #[template_callback]
+        fn on_cancel_clicked(&self, _button: &gtk::Button) {
+            self.respond(gtk::ResponseType::Cancel);
+        }
+
+        #[template_callback]
+        fn on_print_clicked(&self, _button: &gtk::Button) {
+            self.respond(gtk::ResponseType::Ok);
+        }
+
+        /// Report the outcome once. Closing the window after a response does not cancel it.
+        fn respond(&self, response: gtk::ResponseType) {
+            if self.responded.replace(true) {
+                return;
+            }
+            if let Some(callback) = self.on_response.borrow().as_ref() {
+                callback(response);
+            }
+        }
+
+        pub fn add_printer(&self, printer: &gtk::Printer) {
+            glib::g_debug!(LOG_DOMAIN, "Found printer {}", printer.name());
+            let position = self.printer_names.n_items();
+            self.printer_names.append(&printer.name());
+            self.printers.borrow_mut().push(printer.clone());
+
+            let preferred = self.preferred_printer.borrow().clone();
+            let is_preferred = match preferred {
+                Some(name) => printer.name() == name,
+                None => printer.is_default(),
+            };
+            if is_preferred {
+                self.printer_row.set_selected(position);
+            }
+            self.print_btn.set_sensitive(true);
+        }
+
+        pub fn set_paper_sizes(&self, current: &gtk::PaperSize) {
+            let mut paper_sizes = gtk::PaperSize::paper_sizes(false);
+            let position = paper_sizes
+                .iter()
+                .position(|paper_size| paper_size.name() == current.name())
+                .unwrap_or_else(|| {
+                    paper_sizes.insert(0, current.clone());
+                    0
+                });
+            for paper_size in &paper_sizes {
+                self.paper_names.append(&paper_size.display_name());
+            }
+            self.paper_row
+                .set_selected(u32::try_from(position).unwrap_or(0));
+            *self.paper_sizes.borrow_mut() = paper_sizes;
+        }
+
+        pub fn selected_paper_size(&self) -> gtk::PaperSize {
+            let position = usize::try_from(self.paper_row.selected()).unwrap_or(0);
+            self.paper_sizes
+                .borrow()
+                .get(position)
+                .cloned()
+                .unwrap_or_else(|| gtk::PaperSize::new(None))
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct PrintWindow(ObjectSubclass<imp::PrintWindow>)
+        @extends adw::Window, gtk::Window, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Native, gtk::Root, gtk::ShortcutManager;
+}
+
+impl PrintWindow {
+    /// Show `settings` and `page_setup` from the request, with `accept_label` on the print button
+    /// if the app gave one.
+    #[must_use]
+    pub fn new(
+        title: &str,
+        settings: &gtk::PrintSettings,
+        page_setup: &gtk::PageSetup,
+        accept_label: Option<&str>,
+    ) -> Self {
+        let window: Self = glib::Object::builder().build();
+        let imp = window.imp();
+
+        window.set_title(Some(title));
+        imp.window_title.set_title(title);
+        if let Some(accept_label) = accept_label {
+            imp.print_btn.set_label(accept_label);
+        }
+
+        *imp.preferred_printer.borrow_mut() = settings.printer().map(String::from);
+        imp.copies_row
+            .set_value(f64::from(settings.n_copies().max(1)));
+        *imp.settings.borrow_mut() = Some(settings.copy());
+
+        imp.set_paper_sizes(&page_setup.paper_size());
+        imp.orientation_row
+            .set_selected(orientation_position(page_setup.orientation()));
+        imp.top_row.set_value(page_setup.top_margin(gtk::Unit::Mm));
+        imp.bottom_row
+            .set_value(page_setup.bottom_margin(gtk::Unit::Mm));
+        imp.left_row
+            .set_value(page_setup.left_margin(gtk::Unit::Mm));
+        imp.right_row
+            .set_value(page_setup.right_margin(gtk::Unit::Mm));
+
+        // Looking up printers can take a while, so they are added as they are found. The callback
+        // runs on the main thread, but has to be `Send` all the same.
+        let this = glib::SendWeakRef::from(window.downgrade());
+        gtk::enumerate_printers(
+            move |printer| {
+                let Some(window) = this.upgrade() else {
+                    return true;
+                };
+                window.imp().add_printer(printer);
+                false
+            },
+            false,
+        );
+
+        window
+    }
+
+    /// Call `callback` with the outcome once the user prints or cancels.
+    pub fn connect_response<F: Fn(gtk::ResponseType) + 'static>(&self, callback: F) {
+        *self.imp().on_response.borrow_mut() = Some(Box::new(callback));
+    }
+
+    #[must_use]
+    pub fn selected_printer(&self) -> Option<gtk::Printer> {
+        let imp = self.imp();
+        let position = usize::try_from(imp.printer_row.selected()).ok()?;
+        imp.printers.borrow().get(position).cloned()
+    }
+
+    /// The settings of the request with the choices of the user applied.
+    #[must_use]
+    pub fn settings(&self) -> gtk::PrintSettings {
+        let imp = self.imp();
+        let settings = imp
+            .settings
+            .borrow()
+            .as_ref()
+            .map_or_else(gtk::PrintSettings::new, gtk::PrintSettings::copy);
+        if let Some(printer) = self.selected_printer() {
+            settings.set_printer(&printer.name());
+        }
+        // The spin row keeps the value within 1 and 999.
+        #[allow(clippy::cast_possible_truncation)]
+        settings.set_n_copies(imp.copies_row.value() as i32);
+        settings.set_orientation(orientation_at(imp.orientation_row.selected()));
+        settings.set_paper_size(&imp.selected_paper_size());
+        settings
+    }
+
+    #[must_use]
+    pub fn page_setup(&self) -> gtk::PageSetup {
+        let imp = self.imp();
+        let page_setup = gtk::PageSetup::new();
+        page_setup.set_paper_size(&imp.selected_paper_size());
+        page_setup.set_orientation(orientation_at(imp.orientation_row.selected()));
+        page_setup.set_top_margin(imp.top_row.value(), gtk::Unit::Mm);
+        page_setup.set_bottom_margin(imp.bottom_row.value(), gtk::Unit::Mm);
+        page_setup.set_left_margin(imp.left_row.value(), gtk::Unit::Mm);
+        page_setup.set_right_margin(imp.right_row.value(), gtk::Unit::Mm);
+        page_setup
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::responders::test_utils;
+
+    #[test]
+    fn test_orientation_position() {
+        for orientation in ORIENTATIONS {
+            assert_eq!(
+                orientation_at(orientation_position(orientation)),
+                orientation
+            );
+        }
+        assert_eq!(orientation_at(42), gtk::PageOrientation::Portrait);
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_page_setup() {
+        test_utils::run(|| {
+            let page_setup = gtk::PageSetup::new();
+            page_setup.set_paper_size(&gtk::PaperSize::new(Some("iso_a5")));
+            page_setup.set_orientation(gtk::PageOrientation::Landscape);
+            page_setup.set_top_margin(12.0, gtk::Unit::Mm);
+            let settings = gtk::PrintSettings::new();
+            settings.set_n_copies(3);
+
+            let window = PrintWindow::new("Document", &settings, &page_setup, None);
+            let page_setup = window.page_setup();
+            assert_eq!(page_setup.paper_size().name(), "iso_a5");
+            assert_eq!(page_setup.orientation(), gtk::PageOrientation::Landscape);
+            assert!((page_setup.top_margin(gtk::Unit::Mm) - 12.0).abs() < 0.01);
+            assert_eq!(window.settings().n_copies(), 3);
+            window.close();
+        });
+    }
+}
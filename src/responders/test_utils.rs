@@ -0,0 +1,101 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use ashpd::backend::Result;
+use ashpd::zvariant::serialized::Context;
+use ashpd::zvariant::{to_bytes, OwnedValue, Type, Value, LE};
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot::error::TryRecvError;
+use tokio::sync::oneshot::{self, Sender};
+
+use crate::{Application, Request, Responder};
+
+/*
+ * Helpers to exercise responders from unit tests.
+ *
+ * `run` executes the test body on the GTK test thread with the library initialized. `respond`
+ * hands a crafted request to a responder and iterates the main context until the reply arrives.
+ * Since responders create real windows, tests using these helpers need a display.
+ */
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn run<F>(function: F)
+where
+    F: FnOnce() + Send + std::panic::UnwindSafe + 'static,
+{
+    gtk::test_synced(move || {
+        crate::init().unwrap();
+        function();
+    });
+}
+
+pub fn application() -> Application {
+    Application {
+        app_id: None,
+        window_identifier: None,
+    }
+}
+
+/// Convert a value the same way the D-Bus layer does, for types that can only be deserialized.
+pub fn from_value<S, T>(value: &S) -> T
+where
+    S: Serialize + Type,
+    T: for<'de> Deserialize<'de> + Type,
+{
+    let ctxt = Context::new_dbus(LE, 0);
+    let data = to_bytes(ctxt, value).unwrap();
+    let (value, _) = data.deserialize::<T>().unwrap();
+    value
+}
+
+/// Build portal options the same way the D-Bus layer does, by deserializing an `a{sv}` dictionary.
+pub fn options<'a, T>(entries: &[(&str, Value<'a>)]) -> T
+where
+    T: for<'de> Deserialize<'de> + Type,
+{
+    let dict: HashMap<&str, &Value<'a>> = entries.iter().map(|(k, v)| (*k, v)).collect();
+    from_value(&dict)
+}
+
+/// Inspect a portal reply the same way the D-Bus layer sees it, as an `a{sv}` dictionary.
+pub fn to_dict<T>(value: &T) -> HashMap<String, OwnedValue>
+where
+    T: Serialize + Type,
+{
+    let ctxt = Context::new_dbus(LE, 0);
+    let data = to_bytes(ctxt, value).unwrap();
+    let (dict, _) = data.deserialize().unwrap();
+    dict
+}
+
+pub fn respond<T, R, F>(responder: &R, build_request: F) -> Result<T>
+where
+    R: Responder,
+    F: FnOnce(Sender<Result<T>>) -> Request,
+{
+    let (sender, mut receiver) = oneshot::channel();
+    responder.respond(build_request(sender));
+
+    let context = glib::MainContext::default();
+    let start = Instant::now();
+    loop {
+        match receiver.try_recv() {
+            Ok(result) => return result,
+            Err(TryRecvError::Closed) => panic!("Responder dropped the sender without replying"),
+            Err(TryRecvError::Empty) => {
+                assert!(start.elapsed() < TIMEOUT, "Responder did not reply in time");
+                context.iteration(false);
+            }
+        }
+    }
+}
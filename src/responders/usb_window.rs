@@ -0,0 +1,264 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::cell::RefCell;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use ashpd::backend::usb::AccessOptions;
+use ashpd::backend::Result;
+use ashpd::desktop::usb::UsbDevice;
+use ashpd::PortalError;
+use gtk::glib::subclass::InitializingObject;
+use gtk::{glib, CompositeTemplate, TemplateChild};
+
+use crate::utils::{escape_markup, get_application_name, gettextf, parent_window};
+use crate::{Reply, Request, Responder};
+
+/*
+ * `UsbWindow` handles the Usb interface. It lists the devices an app wants to acquire, each with a
+ * switch, so that the user can allow them one by one. The device details come from the frontend,
+ * which also keeps track of the acquired devices and releases them with the session.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-usb-window";
+
+/// Describe the device for display, preferring the vendor and model names from udev.
+fn device_title(id: &str, device: &UsbDevice) -> String {
+    match (device.vendor(), device.model()) {
+        (Some(vendor), Some(model)) => format!("{vendor} {model}"),
+        (Some(name), None) | (None, Some(name)) => name,
+        (None, None) => device.device_file().unwrap_or(id).to_string(),
+    }
+}
+
+/// The devices granted to the app along with their access.
+type Grants = Vec<(String, AccessOptions)>;
+
+/// A device row with the id and write access the app asked for.
+pub struct DeviceRow {
+    id: String,
+    writable: bool,
+    row: adw::SwitchRow,
+}
+
+mod imp {
+    #[allow(clippy::wildcard_imports)]
+    use super::*;
+
+    #[derive(CompositeTemplate, Default)]
+    #[template(resource = "/mobi/phosh/xdpp/ui/usb_window.ui")]
+    pub struct UsbWindow {
+        #[template_child]
+        pub cancel_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub allow_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub devices_group: TemplateChild<adw::PreferencesGroup>,
+
+        pub rows: RefCell<Vec<DeviceRow>>,
+
+        pub reply: Reply<Grants>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for UsbWindow {
+        const NAME: &'static str = "XdppUsbWindow";
+        type Type = super::UsbWindow;
+        type ParentType = adw::Window;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for UsbWindow {}
+
+    impl WidgetImpl for UsbWindow {}
+
+    impl WindowImpl for UsbWindow {}
+
+    impl AdwWindowImpl for UsbWindow {}
+
+    #[gtk::template_callbacks]
+    impl UsbWindow {
+        #[template_callback]
+        fn on_cancel_clicked(&self, _button: &gtk::Button) {
+            let error = PortalError::Cancelled(String::from("Cancelled by user"));
+            self.send_response(Err(error));
+        }
+
+        #[template_callback]
+        fn on_allow_clicked(&self, _button: &gtk::Button) {
+            let devices = self
+                .rows
+                .borrow()
+                .iter()
+                .filter(|device| device.row.is_active())
+                .map(|device| (device.id.clone(), AccessOptions::new(device.writable)))
+                .collect();
+            self.send_response(Ok(devices));
+        }
+
+        pub fn send_response(&self, response: Result<Grants>) {
+            self.reply.send(response);
+            self.obj().close();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct UsbWindow(ObjectSubclass<imp::UsbWindow>)
+        @extends adw::Window, gtk::Window, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Native, gtk::Root, gtk::ShortcutManager;
+}
+
+impl UsbWindow {
+    #[must_use]
+    pub fn new() -> Self {
+        glib::Object::builder().build()
+    }
+}
+
+impl Default for UsbWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Responder for UsbWindow {
+    fn respond(&self, request: Request) {
+        if let Request::UsbAcquireDevices {
+            application,
+            devices,
+            sender,
+        } = request
+        {
+            let imp = self.imp();
+
+            let desc = match get_application_name(&application) {
+                Some(app_name) => gettextf("{} wants to use these devices.", &[&app_name]),
+                None => gettextf("An app wants to use these devices.", &[]),
+            };
+            imp.devices_group
+                .set_description(Some(&escape_markup(&desc)));
+            self.update_property(&[gtk::accessible::Property::Description(&desc)]);
+
+            let mut rows = imp.rows.borrow_mut();
+            for (id, device, access) in devices {
+                let writable = access.is_writable().unwrap_or(false);
+                let row = adw::SwitchRow::builder()
+                    .title(escape_markup(&device_title(&id, &device)))
+                    .active(true)
+                    .build();
+                if writable {
+                    row.set_subtitle(&gettextf("Read and write access", &[]));
+                } else {
+                    row.set_subtitle(&gettextf("Read access", &[]));
+                }
+                imp.devices_group.add(&row);
+                rows.push(DeviceRow { id, writable, row });
+            }
+
+            imp.reply.set(sender);
+
+            parent_window(self, &application, None);
+
+            self.present();
+        } else {
+            glib::g_critical!(LOG_DOMAIN, "Unknown request {request:#?}");
+            panic!();
+        }
+    }
+
+    fn cancel(&self) {
+        let error = PortalError::Cancelled(String::from("Cancelled by application"));
+        self.imp().send_response(Err(error));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use ashpd::zvariant::Value;
+    use tokio::sync::oneshot;
+
+    use super::*;
+    use crate::responders::test_utils;
+
+    #[test]
+    fn test_device_title() {
+        let properties = HashMap::from([
+            ("ID_VENDOR_FROM_DATABASE", Value::from("Phosh")),
+            ("ID_MODEL_FROM_DATABASE", Value::from("Phone")),
+        ]);
+        let device: UsbDevice = test_utils::options(&[("properties", Value::from(properties))]);
+        assert_eq!(device_title("1", &device), "Phosh Phone");
+
+        let device: UsbDevice =
+            test_utils::options(&[("device-file", Value::from("/dev/bus/usb/001/002"))]);
+        assert_eq!(device_title("2", &device), "/dev/bus/usb/001/002");
+
+        let device: UsbDevice = test_utils::options(&[]);
+        assert_eq!(device_title("3", &device), "3");
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_cancel() {
+        test_utils::run(|| {
+            let window = UsbWindow::new();
+            let (sender, mut receiver) = oneshot::channel();
+            window.respond(Request::UsbAcquireDevices {
+                application: test_utils::application(),
+                devices: Vec::new(),
+                sender,
+            });
+
+            window.cancel();
+            assert!(matches!(
+                receiver.try_recv(),
+                Ok(Err(PortalError::Cancelled(_)))
+            ));
+        });
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_rtl_buttons() {
+        test_utils::run(|| {
+            // The header bar mirrors its slots, so the primary action follows the reading order.
+            gtk::Widget::set_default_direction(gtk::TextDirection::Rtl);
+            let window = UsbWindow::new();
+            window.present();
+            let imp = window.imp();
+            let context = glib::MainContext::default();
+            let start = std::time::Instant::now();
+            while imp.allow_btn.width() == 0 || imp.cancel_btn.width() == 0 {
+                assert!(
+                    start.elapsed().as_secs() < 5,
+                    "Window was not laid out in time"
+                );
+                context.iteration(false);
+            }
+
+            let cancel = imp.cancel_btn.compute_bounds(&window).unwrap();
+            let allow = imp.allow_btn.compute_bounds(&window).unwrap();
+            assert!(allow.x() < cancel.x());
+
+            window.close();
+            gtk::Widget::set_default_direction(gtk::TextDirection::Ltr);
+        });
+    }
+}
@@ -0,0 +1,217 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use ashpd::backend::Result;
+use ashpd::desktop::HandleToken;
+use ashpd::PortalError;
+use gtk::glib;
+
+/*
+ * Bookkeeping for session based portals.
+ *
+ * A session outlives the request that created it, so it can not live in the `map` of the
+ * `Requester`. Instead, a session requester keeps a `Sessions` next to it, keyed by the session
+ * handle token. The state of each session is up to the portal. When the frontend closes the
+ * session, the requester gets notified through `SessionImpl::session_closed` and calls `close`,
+ * which runs the teardown callbacks registered on the session and hands back its state.
+ */
+
+const LOG_DOMAIN: &str = "xdpp-session";
+
+type Teardown = Box<dyn FnOnce() + Send + Sync>;
+
+/// A session along with the state the portal associates with it.
+pub struct Session<T> {
+    state: T,
+    teardown: Vec<Teardown>,
+}
+
+impl<T> Session<T> {
+    fn new(state: T) -> Self {
+        Self {
+            state,
+            teardown: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn state(&self) -> &T {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut T {
+        &mut self.state
+    }
+
+    /// Run `callback` when the session is closed. Callbacks run in the order they were added.
+    pub fn on_close(&mut self, callback: impl FnOnce() + Send + Sync + 'static) {
+        self.teardown.push(Box::new(callback));
+    }
+}
+
+/// The sessions of a portal, keyed by their handle token.
+pub struct Sessions<T> {
+    map: RwLock<HashMap<HandleToken, Session<T>>>,
+}
+
+impl<T> Sessions<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            map: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Start tracking a session with the given state.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PortalError::Exist` if a session with the same token is already tracked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread panicked while holding the sessions.
+    pub fn create(&self, token: HandleToken, state: T) -> Result<()> {
+        let mut map = self.map.write().unwrap();
+        if map.contains_key(&token) {
+            glib::g_warning!(LOG_DOMAIN, "Session {token} already exists");
+            return Err(PortalError::Exist(format!(
+                "Session {token} already exists"
+            )));
+        }
+        glib::g_debug!(LOG_DOMAIN, "Created session {token}");
+        map.insert(token, Session::new(state));
+        Ok(())
+    }
+
+    /// Whether a session of `token` is tracked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread panicked while holding the sessions.
+    #[must_use]
+    pub fn contains(&self, token: &HandleToken) -> bool {
+        self.map.read().unwrap().contains_key(token)
+    }
+
+    /// Call `function` with the session of `token`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PortalError::NotFound` if there is no such session.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread panicked while holding the sessions.
+    pub fn with<R>(
+        &self,
+        token: &HandleToken,
+        function: impl FnOnce(&mut Session<T>) -> R,
+    ) -> Result<R> {
+        let mut map = self.map.write().unwrap();
+        map.get_mut(token)
+            .map(function)
+            .ok_or_else(|| PortalError::NotFound(format!("Unknown session {token}")))
+    }
+
+    /// Stop tracking the session of `token`, run its teardown callbacks and return its state.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PortalError::NotFound` if there is no such session.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread panicked while holding the sessions.
+    pub fn close(&self, token: &HandleToken) -> Result<T> {
+        // Take the session out before tearing it down, so that callbacks can use the sessions.
+        let session = self.map.write().unwrap().remove(token);
+        let Some(session) = session else {
+            glib::g_warning!(LOG_DOMAIN, "Unknown session {token}");
+            return Err(PortalError::NotFound(format!("Unknown session {token}")));
+        };
+        glib::g_debug!(LOG_DOMAIN, "Closing session {token}");
+        for callback in session.teardown {
+            callback();
+        }
+        Ok(session.state)
+    }
+}
+
+impl<T> Default for Sessions<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn token(name: &str) -> HandleToken {
+        HandleToken::try_from(name).unwrap()
+    }
+
+    #[test]
+    fn test_create_and_lookup() {
+        let sessions = Sessions::new();
+        sessions.create(token("first"), 1).unwrap();
+        assert!(sessions.contains(&token("first")));
+        assert!(!sessions.contains(&token("second")));
+        assert!(matches!(
+            sessions.create(token("first"), 2),
+            Err(PortalError::Exist(_))
+        ));
+
+        sessions
+            .with(&token("first"), |session| *session.state_mut() += 1)
+            .unwrap();
+        assert_eq!(
+            sessions
+                .with(&token("first"), |session| *session.state())
+                .unwrap(),
+            2
+        );
+        assert!(matches!(
+            sessions.with(&token("second"), |_| ()),
+            Err(PortalError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_close() {
+        let closed = Arc::new(AtomicUsize::new(0));
+        let sessions = Sessions::new();
+        sessions.create(token("first"), "state").unwrap();
+        for _ in 0..2 {
+            let closed = closed.clone();
+            sessions
+                .with(&token("first"), |session| {
+                    session.on_close(move || {
+                        closed.fetch_add(1, Ordering::SeqCst);
+                    });
+                })
+                .unwrap();
+        }
+
+        assert_eq!(sessions.close(&token("first")).unwrap(), "state");
+        assert_eq!(closed.load(Ordering::SeqCst), 2);
+        assert!(!sessions.contains(&token("first")));
+        assert!(matches!(
+            sessions.close(&token("first")),
+            Err(PortalError::NotFound(_))
+        ));
+        assert_eq!(closed.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,44 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+/* Include the source to get at the static helpers */
+#include "pmp-inhibit.c"
+
+
+static void
+test_build_state (void)
+{
+  g_autoptr (GVariant) state = NULL;
+  gboolean active;
+  guint32 session_state;
+
+  state = g_variant_ref_sink (build_state (TRUE, PMP_SESSION_STATE_QUERY_END));
+  g_assert_true (g_variant_is_of_type (state, G_VARIANT_TYPE_VARDICT));
+
+  g_assert_true (g_variant_lookup (state, "screensaver-active", "b", &active));
+  g_assert_true (active);
+  g_assert_true (g_variant_lookup (state, "session-state", "u", &session_state));
+  g_assert_cmpuint (session_state, ==, 2);
+
+  g_clear_pointer (&state, g_variant_unref);
+  state = g_variant_ref_sink (build_state (FALSE, PMP_SESSION_STATE_RUNNING));
+
+  g_assert_true (g_variant_lookup (state, "screensaver-active", "b", &active));
+  g_assert_false (active);
+  g_assert_true (g_variant_lookup (state, "session-state", "u", &session_state));
+  g_assert_cmpuint (session_state, ==, 1);
+}
+
+
+int
+main (int argc, char *argv[])
+{
+  g_test_init (&argc, &argv, NULL);
+
+  g_test_add_func ("/pmp/inhibit/build-state", test_build_state);
+
+  return g_test_run ();
+}
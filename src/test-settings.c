@@ -0,0 +1,72 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+/* Include the source to get at the static helpers */
+#include "pmp-settings.c"
+
+
+static void
+on_setting_changed (PmpImplSettings *impl,
+                    const char      *namespace,
+                    const char      *key,
+                    GVariant        *value,
+                    gpointer         user_data)
+{
+  GHashTable *emitted = user_data;
+
+  g_hash_table_insert (emitted, g_strdup (key), g_variant_ref (value));
+}
+
+
+static void
+test_coalesce_changes (void)
+{
+  g_autoptr (PmpImplSettings) impl = pmp_impl_settings_skeleton_new ();
+  g_autoptr (GHashTable) emitted = NULL;
+  PendingChange *change;
+  GVariant *value;
+
+  pending_changes = g_hash_table_new_full (g_str_hash, g_str_equal, g_free,
+                                           (GDestroyNotify)pending_change_free);
+  emitted = g_hash_table_new_full (g_str_hash, g_str_equal, g_free,
+                                   (GDestroyNotify)g_variant_unref);
+  g_signal_connect (impl, "setting-changed", G_CALLBACK (on_setting_changed), emitted);
+
+  queue_setting_changed (impl, "org.freedesktop.appearance", "color-scheme", g_variant_new_uint32 (0));
+  queue_setting_changed (impl, "org.freedesktop.appearance", "color-scheme", g_variant_new_uint32 (2));
+  queue_setting_changed (impl, "org.freedesktop.appearance", "contrast", g_variant_new_uint32 (1));
+  queue_setting_changed (impl, "org.freedesktop.appearance", "color-scheme", g_variant_new_uint32 (1));
+
+  /* One pending change per key, holding the last value */
+  g_assert_cmpuint (g_hash_table_size (pending_changes), ==, 2);
+  change = g_hash_table_lookup (pending_changes, "org.freedesktop.appearance color-scheme");
+  g_assert_nonnull (change);
+  g_assert_cmpuint (g_variant_get_uint32 (change->value), ==, 1);
+  g_assert_cmpuint (g_hash_table_size (emitted), ==, 0);
+
+  while (pending_changes_id != 0)
+    g_main_context_iteration (NULL, TRUE);
+
+  g_assert_cmpuint (g_hash_table_size (pending_changes), ==, 0);
+  g_assert_cmpuint (g_hash_table_size (emitted), ==, 2);
+  value = g_hash_table_lookup (emitted, "color-scheme");
+  g_assert_cmpuint (g_variant_get_uint32 (value), ==, 1);
+  value = g_hash_table_lookup (emitted, "contrast");
+  g_assert_cmpuint (g_variant_get_uint32 (value), ==, 1);
+
+  g_clear_pointer (&pending_changes, g_hash_table_destroy);
+}
+
+
+int
+main (int argc, char *argv[])
+{
+  g_test_init (&argc, &argv, NULL);
+
+  g_test_add_func ("/pmp/settings/coalesce-changes", test_coalesce_changes);
+
+  return g_test_run ();
+}
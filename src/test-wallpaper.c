@@ -0,0 +1,43 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+/* Include the source to get at the static helpers */
+#include "pmp-wallpaper.c"
+
+
+static void
+test_get_target_schemas (void)
+{
+  const char * const *schemas;
+
+  schemas = get_target_schemas (NULL);
+  g_assert_cmpuint (g_strv_length ((GStrv)schemas), ==, 2);
+  g_assert_true (g_strv_contains (schemas, BACKGROUND_SCHEMA));
+  g_assert_true (g_strv_contains (schemas, SCREENSAVER_SCHEMA));
+
+  g_assert_true (get_target_schemas ("both") == schemas);
+
+  schemas = get_target_schemas ("background");
+  g_assert_cmpuint (g_strv_length ((GStrv)schemas), ==, 1);
+  g_assert_cmpstr (schemas[0], ==, BACKGROUND_SCHEMA);
+
+  schemas = get_target_schemas ("lockscreen");
+  g_assert_cmpuint (g_strv_length ((GStrv)schemas), ==, 1);
+  g_assert_cmpstr (schemas[0], ==, SCREENSAVER_SCHEMA);
+
+  g_assert_null (get_target_schemas ("everywhere"));
+}
+
+
+int
+main (int argc, char *argv[])
+{
+  g_test_init (&argc, &argv, NULL);
+
+  g_test_add_func ("/pmp/wallpaper/target-schemas", test_get_target_schemas);
+
+  return g_test_run ();
+}
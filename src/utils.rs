@@ -6,16 +6,38 @@
  * Author: Arun Mani J <arun.mani@tether.to>
  */
 
+use std::cell::Cell;
+use std::future::Future;
+use std::rc::Rc;
+
+use adw::prelude::*;
+use ashpd::desktop::file_chooser::Choice;
+use ashpd::{AppID, PortalError};
 use gettextrs::gettext;
-use gio::prelude::*;
-use gtk::gio;
+use gtk::glib;
 
+use crate::defaults::default_modal;
 use crate::Application;
 
 /*
  * Utility functions that are used in more than one place.
  */
 
+const LOG_DOMAIN: &str = "xdpp-utils";
+
+/// The longest app-id there can be, as it has to be a valid D-Bus name.
+const MAX_APP_ID_LENGTH: usize = 255;
+
+const CONFIRM_RESPONSE: &str = "confirm";
+const CANCEL_RESPONSE: &str = "cancel";
+
+/// Decide the modality of a portal window. The request's choice wins, and [`default_modal`] is used
+/// when it has none. Only a window with a parent can be modal, as there is nothing to block
+/// otherwise.
+fn resolve_modal(modal: Option<bool>, parented: bool) -> bool {
+    parented && modal.unwrap_or_else(default_modal)
+}
+
 // Thanks to Pika Backup.
 // https://gitlab.gnome.org/World/pika-backup/-/blob/81a9b0eefbd5099296b1655cc7a7eb8849153795/src/prelude.rs#L15
 #[must_use]
@@ -28,6 +50,225 @@ pub fn gettextf(format: &str, args: &[&str]) -> String {
     s
 }
 
+/// Escape text for widgets that render Pango markup, like the title and subtitle of rows or the
+/// description of groups. Strings coming from apps must go through it, so that they show up as
+/// written instead of injecting markup.
+///
+/// Example:
+/// ```ignore
+/// assert_eq!(escape_markup("<b>Bold</b>"), "&lt;b&gt;Bold&lt;/b&gt;");
+/// ```
+#[must_use]
+pub fn escape_markup(text: &str) -> String {
+    glib::markup_escape_text(text).into()
+}
+
+/// Turn an accept label given by an app into plain text for display.
+///
+/// Apps often pass GTK style labels. Mnemonic underscores are dropped, with `__` standing for a
+/// literal underscore, and `...` is replaced by an ellipsis.
+///
+/// Example:
+/// ```ignore
+/// assert_eq!(normalize_accept_label("_Open..."), "Open…");
+/// ```
+#[must_use]
+pub fn normalize_accept_label(label: &str) -> String {
+    let mut normalized = String::with_capacity(label.len());
+    let mut chars = label.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '_' {
+            normalized.push(c);
+        } else if chars.next_if_eq(&'_').is_some() {
+            normalized.push('_');
+        }
+    }
+
+    normalized.replace("...", "…").trim().to_string()
+}
+
+/// Normalize the accept label given by an app, or fall back to `default` if there is none.
+#[must_use]
+pub fn accept_label_or(label: Option<&str>, default: &str) -> String {
+    label
+        .map(normalize_accept_label)
+        .filter(|label| !label.is_empty())
+        .unwrap_or_else(|| String::from(default))
+}
+
+/// Whether `value` is an option of `choice`. A choice without options is a checkbox, whose value
+/// is either `true` or `false`.
+#[must_use]
+pub fn is_choice_option(choice: &Choice, value: &str) -> bool {
+    let pairs = choice.pairs();
+    if pairs.is_empty() {
+        value == "true" || value == "false"
+    } else {
+        pairs.iter().any(|(key, _)| *key == value)
+    }
+}
+
+/// The option of `choice` selected initially. An initial selection that is not an option falls back
+/// to the first option, or to `false` for a checkbox.
+#[must_use]
+pub fn initial_choice(choice: &Choice) -> &str {
+    let initial = choice.initial_selection();
+    if is_choice_option(choice, initial) {
+        initial
+    } else if let Some((key, _)) = choice.pairs().first() {
+        key
+    } else {
+        "false"
+    }
+}
+
+/// Pair every choice of the request with the option the user ended up with. A choice the user did
+/// not touch, or that came back with a value that is not an option, keeps its initial selection.
+#[must_use]
+pub fn resolve_choices(choices: &[Choice], selected: &[(String, String)]) -> Vec<(String, String)> {
+    choices
+        .iter()
+        .map(|choice| {
+            let value = selected
+                .iter()
+                .find(|(id, _)| id == choice.id())
+                .map(|(_, value)| value.as_str())
+                .filter(|value| is_choice_option(choice, value))
+                .unwrap_or_else(|| initial_choice(choice));
+            (choice.id().to_owned(), value.to_owned())
+        })
+        .collect()
+}
+
+/// Whether the address looks like an email address that can go into a `mailto:` URI.
+///
+/// This is not a full RFC 5322 check. It asks for a single `@` between a local part and a dotted
+/// domain, without spaces or characters that separate addresses.
+#[must_use]
+pub fn is_valid_email(address: &str) -> bool {
+    let Some((local, domain)) = address.split_once('@') else {
+        return false;
+    };
+
+    let allowed = |c: char| !c.is_whitespace() && !c.is_control() && !"@,;:<>()[]\\\"".contains(c);
+    !local.is_empty()
+        && local.chars().all(allowed)
+        && domain.contains('.')
+        && domain.split('.').all(|label| !label.is_empty())
+        && domain.chars().all(allowed)
+}
+
+/// Check an app-id coming from outside, like the desktop IDs given by the frontend, and convert it.
+///
+/// # Errors
+///
+/// Returns `PortalError::InvalidArgument` if the app-id is empty, too long, looks like a path or is
+/// otherwise invalid.
+pub fn parse_app_id(app_id: &str) -> Result<AppID, PortalError> {
+    let reason = if app_id.is_empty() {
+        "App-id is empty"
+    } else if app_id.len() > MAX_APP_ID_LENGTH {
+        "App-id is too long"
+    } else if app_id.contains(['/', '\\', '\0']) || app_id.contains("..") {
+        "App-id looks like a path"
+    } else {
+        return app_id
+            .parse()
+            .map_err(|_| PortalError::InvalidArgument(String::from("App-id is invalid")));
+    };
+    glib::g_debug!(
+        LOG_DOMAIN,
+        "Rejecting app-id `{}`: {reason}",
+        app_id.escape_debug()
+    );
+    Err(PortalError::InvalidArgument(String::from(reason)))
+}
+
+fn confirmation_dialog(
+    title: &str,
+    body: &str,
+    confirm_label: &str,
+    destructive: bool,
+) -> adw::AlertDialog {
+    let dialog = adw::AlertDialog::new(Some(title), Some(body));
+    dialog.add_response(CANCEL_RESPONSE, &gettextf("Cancel", &[]));
+    dialog.add_response(CONFIRM_RESPONSE, confirm_label);
+    let appearance = if destructive {
+        adw::ResponseAppearance::Destructive
+    } else {
+        adw::ResponseAppearance::Suggested
+    };
+    dialog.set_response_appearance(CONFIRM_RESPONSE, appearance);
+    dialog.set_close_response(CANCEL_RESPONSE);
+    dialog
+}
+
+/// Ask the user to confirm an action with a dialog on `parent`. Resolves to `true` only if the
+/// action was confirmed, closing the dialog counts as a cancel.
+pub fn confirm(
+    parent: &impl IsA<gtk::Widget>,
+    title: &str,
+    body: &str,
+    confirm_label: &str,
+    destructive: bool,
+) -> impl Future<Output = bool> {
+    let response =
+        confirmation_dialog(title, body, confirm_label, destructive).choose_future(parent);
+    async move { response.await == CONFIRM_RESPONSE }
+}
+
+/// The busy state of a dialog doing async work, like loading a picture or launching an app. While
+/// any work is in flight, the spinner spins and the widget that would act on the outcome is
+/// insensitive.
+#[derive(Default)]
+pub struct Busy {
+    count: Rc<Cell<usize>>,
+}
+
+impl Busy {
+    /// Mark the start of some work. The returned guard marks its end when dropped, so that the busy
+    /// state is cleared however the work ends.
+    #[must_use]
+    pub fn start(&self, spinner: &gtk::Spinner, widget: &impl IsA<gtk::Widget>) -> BusyGuard {
+        self.count.set(self.count.get() + 1);
+        let guard = BusyGuard {
+            count: self.count.clone(),
+            spinner: spinner.clone(),
+            widget: widget.clone().upcast(),
+        };
+        guard.update();
+        guard
+    }
+
+    #[must_use]
+    pub fn is_busy(&self) -> bool {
+        self.count.get() > 0
+    }
+}
+
+pub struct BusyGuard {
+    count: Rc<Cell<usize>>,
+    spinner: gtk::Spinner,
+    widget: gtk::Widget,
+}
+
+impl BusyGuard {
+    fn update(&self) {
+        let busy = self.count.get() > 0;
+        self.spinner.set_visible(busy);
+        self.spinner.set_spinning(busy);
+        self.widget.set_sensitive(!busy);
+    }
+}
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        self.count.set(self.count.get().saturating_sub(1));
+        self.update();
+    }
+}
+
 #[must_use]
 pub fn get_application_name(application: &Application) -> Option<String> {
     let app_id = application.app_id.as_ref()?;
@@ -35,3 +276,257 @@ pub fn get_application_name(application: &Application) -> Option<String> {
     let app_name = app_info.display_name().to_string();
     Some(app_name)
 }
+
+/// Log the outputs `window` gets shown on, to check the placement on devices with more than one.
+fn log_monitors(window: &gtk::Window) {
+    let connect = |window: &gtk::Window| {
+        if let Some(surface) = window.surface() {
+            surface.connect_enter_monitor(|_, monitor| {
+                glib::g_debug!(
+                    LOG_DOMAIN,
+                    "Window entered monitor {}",
+                    monitor.connector().unwrap_or_default()
+                );
+            });
+        }
+    };
+
+    if window.is_realized() {
+        connect(window);
+    } else {
+        window.connect_realize(move |window| connect(window));
+    }
+}
+
+/// Attach `window` to the surface of the requesting application and set its modality.
+///
+/// `modal` is the modality requested by the app, if any. Requests without a usable window
+/// identifier are common when the caller is not a graphical app. The window is then shown as a
+/// regular, non-modal toplevel. Presenting it afterwards still gives it the focus. Returns whether
+/// the window got a parent.
+///
+/// Clients can not pick the output of a toplevel on Wayland, so the placement is up to the
+/// compositor. A window with a parent follows the parent to its output, like when Phosh is docked
+/// to an external display. A window without one is shown on the output that has the keyboard
+/// focus, which is where the user interacted last. The outputs are logged to verify this.
+pub fn parent_window(
+    window: &impl IsA<gtk::Window>,
+    application: &Application,
+    modal: Option<bool>,
+) -> bool {
+    let window = window.as_ref();
+    let parented = application
+        .window_identifier
+        .as_ref()
+        .is_some_and(|identifier| identifier.set_parent_of(window));
+
+    if !parented {
+        glib::g_debug!(LOG_DOMAIN, "Presenting window without a parent");
+        window.set_transient_for(gtk::Window::NONE);
+    }
+
+    let resolved = resolve_modal(modal, parented);
+    if modal.unwrap_or_else(default_modal) && !resolved {
+        // A modal window without a parent could not be dismissed by going back to the app.
+        glib::g_debug!(
+            LOG_DOMAIN,
+            "Presenting window as non-modal since it has no parent"
+        );
+    }
+    window.set_modal(resolved);
+    log_monitors(window);
+
+    parented
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_busy() {
+        crate::responders::test_utils::run(|| {
+            let busy = Busy::default();
+            let spinner = gtk::Spinner::new();
+            let button = gtk::Button::new();
+
+            let first = busy.start(&spinner, &button);
+            let second = busy.start(&spinner, &button);
+            drop(first);
+            // Work is still in flight.
+            assert!(busy.is_busy());
+            assert!(spinner.is_spinning());
+            assert!(!button.is_sensitive());
+
+            drop(second);
+            assert!(!busy.is_busy());
+            assert!(!spinner.is_visible());
+            assert!(button.is_sensitive());
+        });
+    }
+
+    #[test]
+    fn test_escape_markup() {
+        assert_eq!(escape_markup("<b>Bold</b>"), "&lt;b&gt;Bold&lt;/b&gt;");
+        assert_eq!(escape_markup("Tom & Jerry"), "Tom &amp; Jerry");
+        assert_eq!(escape_markup("Plain"), "Plain");
+    }
+
+    #[test]
+    fn test_resolve_choices() {
+        let choices = [
+            Choice::boolean("reencode", "Re-encode", true),
+            Choice::new("encoding", "Encoding", "latin15")
+                .insert("utf8", "Unicode (UTF-8)")
+                .insert("latin15", "Western"),
+            Choice::new("untouched", "Untouched", "")
+                .insert("first", "First")
+                .insert("second", "Second"),
+        ];
+        let expected = |reencode: &str, encoding: &str| {
+            vec![
+                (String::from("reencode"), String::from(reencode)),
+                (String::from("encoding"), String::from(encoding)),
+                (String::from("untouched"), String::from("first")),
+            ]
+        };
+
+        assert_eq!(resolve_choices(&choices, &[]), expected("true", "latin15"));
+
+        let selected = [
+            (String::from("encoding"), String::from("utf8")),
+            (String::from("reencode"), String::from("false")),
+            (String::from("unknown"), String::from("value")),
+        ];
+        assert_eq!(
+            resolve_choices(&choices, &selected),
+            expected("false", "utf8")
+        );
+
+        let selected = [
+            (String::from("reencode"), String::from("yes")),
+            (String::from("encoding"), String::from("ascii")),
+        ];
+        assert_eq!(
+            resolve_choices(&choices, &selected),
+            expected("true", "latin15")
+        );
+        assert_eq!(initial_choice(&Choice::new("check", "Check", "")), "false");
+    }
+
+    #[test]
+    fn test_normalize_accept_label() {
+        assert_eq!(normalize_accept_label("_Open"), "Open");
+        assert_eq!(normalize_accept_label("Save _As..."), "Save As…");
+        assert_eq!(normalize_accept_label("my__file"), "my_file");
+        assert_eq!(normalize_accept_label("Select"), "Select");
+        assert_eq!(normalize_accept_label(" _ "), "");
+    }
+
+    #[test]
+    fn test_is_valid_email() {
+        assert!(is_valid_email("user@phosh.mobi"));
+        assert!(is_valid_email("first.last+tag@mail.example.org"));
+
+        assert!(!is_valid_email(""));
+        assert!(!is_valid_email("user"));
+        assert!(!is_valid_email("@phosh.mobi"));
+        assert!(!is_valid_email("user@"));
+        assert!(!is_valid_email("user@localhost"));
+        assert!(!is_valid_email("user@phosh..mobi"));
+        assert!(!is_valid_email("user@@phosh.mobi"));
+        assert!(!is_valid_email("us er@phosh.mobi"));
+        assert!(!is_valid_email("a@phosh.mobi, b@phosh.mobi"));
+    }
+
+    #[test]
+    fn test_parse_app_id() {
+        assert_eq!(
+            parse_app_id("org.gnome.Maps").unwrap().to_string(),
+            "org.gnome.Maps"
+        );
+
+        let too_long = format!("org.{}", "a".repeat(MAX_APP_ID_LENGTH));
+        for app_id in [
+            "",
+            too_long.as_str(),
+            "../org.gnome.Maps",
+            "org/gnome/Maps",
+            "org.gnome..Maps",
+            "org.gnome.Maps\0",
+            "org",
+            "org.gnome.Maps!",
+            "0rg.gnome.Maps",
+        ] {
+            assert!(
+                matches!(parse_app_id(app_id), Err(PortalError::InvalidArgument(_))),
+                "{app_id:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_modal() {
+        assert_eq!(resolve_modal(None, true), default_modal());
+        assert!(resolve_modal(Some(true), true));
+        assert!(!resolve_modal(Some(false), true));
+        assert!(!resolve_modal(None, false));
+        assert!(!resolve_modal(Some(true), false));
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_confirm() {
+        gtk::test_synced(|| {
+            crate::init().unwrap();
+            let context = glib::MainContext::default();
+            for (response, confirmed) in [(CONFIRM_RESPONSE, true), (CANCEL_RESPONSE, false)] {
+                let window = adw::Window::new();
+                window.present();
+                let result = std::rc::Rc::new(std::cell::Cell::new(None));
+                let future = confirm(&window, "Title", "Body", "Confirm", true);
+                context.spawn_local(glib::clone!(
+                    #[strong]
+                    result,
+                    async move { result.set(Some(future.await)) }
+                ));
+
+                let dialog = loop {
+                    match window.visible_dialog().and_downcast::<adw::AlertDialog>() {
+                        Some(dialog) => break dialog,
+                        None => context.iteration(true),
+                    };
+                };
+                assert_eq!(
+                    dialog.response_appearance(CONFIRM_RESPONSE),
+                    adw::ResponseAppearance::Destructive
+                );
+                // Closing the dialog answers with its close response.
+                dialog.set_close_response(response);
+                dialog.close();
+                while result.get().is_none() {
+                    context.iteration(true);
+                }
+                assert_eq!(result.get(), Some(confirmed));
+                window.close();
+            }
+        });
+    }
+
+    #[test]
+    #[ignore = "requires a display"]
+    fn test_parent_window_without_identifier() {
+        gtk::test_synced(|| {
+            crate::init().unwrap();
+            let application = Application {
+                app_id: None,
+                window_identifier: None,
+            };
+            let window = gtk::Window::new();
+            window.set_modal(true);
+            assert!(!parent_window(&window, &application, Some(true)));
+            assert!(!window.is_modal());
+        });
+    }
+}
@@ -24,6 +24,7 @@
 #include "xdg-desktop-portal-dbus.h"
 
 #include "pmp-file-chooser.h"
+#include "pmp-inhibit.h"
 #include "pmp-request.h"
 #include "pmp-notification.h"
 #include "pmp-settings.h"
@@ -81,6 +82,10 @@ on_bus_acquired (GDBusConnection *connection,
     g_warning ("error: %s\n", error->message);
     g_clear_error (&error);
   }
+  if (!pmp_inhibit_init (connection, &error)) {
+    g_warning ("error: %s\n", error->message);
+    g_clear_error (&error);
+  }
   if (!pmp_notification_init (connection, &error)) {
     g_warning ("error: %s\n", error->message);
     g_clear_error (&error);
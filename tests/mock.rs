@@ -0,0 +1,148 @@
+/*
+ * Copyright (C) 2025 The Phosh Developers
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ *
+ * Author: Arun Mani J <arun.mani@tether.to>
+ */
+
+use std::collections::HashMap;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+use ashpd::url::Url;
+use gtk::glib;
+use tokio::runtime::Runtime;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+/*
+ * Smoke tests of the D-Bus surface. They run the backend in mock mode and call it like the portal
+ * frontend would. Each test runs its own backend under a name of its own, so that tests running in
+ * parallel do not take the name from each other. No display is needed, but a session bus is, for
+ * example:
+ *
+ *   dbus-run-session cargo test --features mock -- --ignored
+ */
+
+const DBUS_NAME: &str = "org.freedesktop.impl.portal.desktop.phroshmock";
+const OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+const REQUEST_PATH: &str = "/org/freedesktop/portal/desktop/request/1_0";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The backend process of a test, stopped once the test is over.
+struct Backend {
+    child: Child,
+    dbus_name: String,
+    request_path: String,
+}
+
+impl Backend {
+    /// Start a backend for the test `name`, owning a bus name derived from it.
+    fn spawn(name: &str) -> Self {
+        let dbus_name = format!("{DBUS_NAME}.{name}");
+        let child = Command::new(env!("CARGO_BIN_EXE_xdg-desktop-portal-phosh"))
+            .arg("--mock")
+            .arg(format!("--dbus-name={dbus_name}"))
+            .spawn()
+            .unwrap();
+        Self {
+            child,
+            dbus_name,
+            request_path: format!("{REQUEST_PATH}/{name}"),
+        }
+    }
+
+    /// Call a method of the backend like the frontend would, retrying while the backend starts
+    /// up. Returns the response code and the results of the request.
+    async fn call<B>(
+        &self,
+        interface: &str,
+        method: &str,
+        body: &B,
+    ) -> (u32, HashMap<String, OwnedValue>)
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        let connection = zbus::Connection::session().await.unwrap();
+
+        // The backend needs a moment to own its name.
+        let start = Instant::now();
+        let reply = loop {
+            let result = connection
+                .call_method(
+                    Some(self.dbus_name.as_str()),
+                    OBJECT_PATH,
+                    Some(interface),
+                    method,
+                    body,
+                )
+                .await;
+            match result {
+                Ok(reply) => break reply,
+                Err(error) => {
+                    assert!(start.elapsed() < TIMEOUT, "Backend did not reply: {error}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        };
+
+        reply.body().deserialize().unwrap()
+    }
+}
+
+impl Drop for Backend {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+#[ignore = "requires a session bus"]
+fn test_open_file() {
+    let backend = Backend::spawn("open_file");
+
+    Runtime::new().unwrap().block_on(async {
+        let handle = OwnedObjectPath::try_from(backend.request_path.as_str()).unwrap();
+        let options: HashMap<&str, Value> = HashMap::new();
+        let (response, results) = backend
+            .call(
+                "org.freedesktop.impl.portal.FileChooser",
+                "OpenFile",
+                &(&handle, "", "", "Open", &options),
+            )
+            .await;
+        assert_eq!(response, 0);
+
+        let uris = <Vec<String>>::try_from(results["uris"].try_clone().unwrap()).unwrap();
+        let expected = Url::from_file_path(glib::home_dir().join("test.txt")).unwrap();
+        assert_eq!(uris, vec![expected.to_string()]);
+    });
+}
+
+#[test]
+#[ignore = "requires a session bus"]
+fn test_get_user_information() {
+    let backend = Backend::spawn("get_user_information");
+
+    Runtime::new().unwrap().block_on(async {
+        let handle = OwnedObjectPath::try_from(backend.request_path.as_str()).unwrap();
+        let options: HashMap<&str, Value> = HashMap::from([("reason", Value::from("Testing"))]);
+        let (response, results) = backend
+            .call(
+                "org.freedesktop.impl.portal.Account",
+                "GetUserInformation",
+                &(&handle, "", "", &options),
+            )
+            .await;
+        assert_eq!(response, 0);
+
+        let id = String::try_from(results["id"].try_clone().unwrap()).unwrap();
+        assert_eq!(id, glib::user_name().to_string_lossy());
+        let name = String::try_from(results["name"].try_clone().unwrap()).unwrap();
+        assert_eq!(name, glib::real_name().to_string_lossy());
+        let image = String::try_from(results["image"].try_clone().unwrap()).unwrap();
+        let expected = Url::from_file_path(glib::home_dir().join(".face")).unwrap();
+        assert_eq!(image, expected.to_string());
+    });
+}